@@ -0,0 +1,236 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use tetris_core::gravity::FRAME_RATE;
+
+use crate::keymap::Keymap;
+use crate::marathon::{MARATHON_LINES, MAX_START_LEVEL};
+use crate::sprint::SPRINT_LINES;
+use crate::{Options, MAX_ARR, MAX_DAS, MAX_PREVIEW, MAX_SDF, ULTRA_SECONDS};
+
+pub const SETTINGS_FILE: &str = "settings.toml";
+pub const KEYS_FILE: &str = "keys.toml";
+
+/// Where config files live, `$XDG_CONFIG_HOME/tetris` on Linux
+pub fn dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("tetris"))
+}
+
+/// Rounds a duration to the nearest whole frame. Done in u64 since any u32
+/// of milliseconds has to convert without overflowing
+pub fn ms_to_frames(ms: u32) -> u32 {
+    ((ms as u64 * FRAME_RATE as u64 + 500) / 1000) as u32
+}
+
+pub fn frames_to_ms(frames: u32) -> u32 {
+    let ms = (frames as u64 * 1000 + FRAME_RATE as u64 / 2) / FRAME_RATE as u64;
+    ms.min(u32::MAX as u64) as u32
+}
+
+/// Converts a das or arr from milliseconds, rejecting one longer than `max`
+/// frames
+pub fn handling_frames(name: &str, ms: u32, max: u32) -> Result<u32, String> {
+    let frames = ms_to_frames(ms);
+    if frames > max {
+        return Err(format!("{} must be 0 to {} ms", name, frames_to_ms(max)));
+    }
+    Ok(frames)
+}
+
+/// The contents of a file, or None if there isn't one
+pub fn read(path: &Path) -> Result<Option<String>, String> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => Ok(Some(text)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("{}: {}", path.display(), e)),
+    }
+}
+
+/// Replaces a file's contents all at once. The new contents are written
+/// beside it and renamed over it, so a crash never leaves a file half written
+pub fn write_atomic(path: &Path, contents: &str) -> Result<(), String> {
+    use std::io::Write;
+
+    let error = |e: std::io::Error| format!("{}: {}", path.display(), e);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(error)?;
+    }
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    let temp = PathBuf::from(temp);
+    let mut file = std::fs::File::create(&temp).map_err(error)?;
+    file.write_all(contents.as_bytes()).map_err(error)?;
+    file.sync_all().map_err(error)?;
+    std::fs::rename(&temp, path).map_err(error)
+}
+
+/// The options kept between runs, as written to `settings.toml`. Times are in
+/// milliseconds like the command line flags
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub das: u32,
+    pub arr: u32,
+    pub sdf: u32,
+    pub preview: usize,
+    pub ghost: bool,
+    pub sprint_lines: usize,
+    pub ultra_seconds: u64,
+    pub start_level: usize,
+    /// Zero for an endless marathon
+    pub marathon_lines: usize,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings::from_options(&Options::default())
+    }
+}
+
+impl Settings {
+    pub fn from_options(options: &Options) -> Settings {
+        let handling = &options.game.handling;
+        Settings {
+            das: frames_to_ms(handling.das),
+            arr: frames_to_ms(handling.arr),
+            sdf: handling.sdf,
+            preview: options.game.preview_count,
+            ghost: options.ghost,
+            sprint_lines: options.sprint_lines,
+            ultra_seconds: options.ultra_seconds,
+            start_level: options.game.start_level,
+            marathon_lines: options.marathon_lines,
+        }
+    }
+
+    pub fn apply(&self, options: &mut Options) -> Result<(), String> {
+        let das = handling_frames("das", self.das, MAX_DAS)?;
+        let arr = handling_frames("arr", self.arr, MAX_ARR)?;
+        if !(1..=MAX_SDF).contains(&self.sdf) {
+            return Err(format!("sdf must be 1 to {}", MAX_SDF));
+        }
+        if !(1..=MAX_PREVIEW).contains(&self.preview) {
+            return Err(format!("preview must be 1 to {}", MAX_PREVIEW));
+        }
+        if !SPRINT_LINES.contains(&self.sprint_lines) {
+            return Err(format!("sprint lines must be one of {:?}", SPRINT_LINES));
+        }
+        if !ULTRA_SECONDS.contains(&self.ultra_seconds) {
+            return Err(format!("ultra seconds must be one of {:?}", ULTRA_SECONDS));
+        }
+        if !(1..=MAX_START_LEVEL).contains(&self.start_level) {
+            return Err(format!("start level must be 1 to {}", MAX_START_LEVEL));
+        }
+        if !MARATHON_LINES.contains(&self.marathon_lines) {
+            return Err(format!(
+                "marathon lines must be one of {:?}",
+                MARATHON_LINES
+            ));
+        }
+        let handling = &mut options.game.handling;
+        handling.das = das;
+        handling.arr = arr;
+        handling.sdf = self.sdf;
+        options.game.preview_count = self.preview;
+        options.ghost = self.ghost;
+        options.sprint_lines = self.sprint_lines;
+        options.ultra_seconds = self.ultra_seconds;
+        options.game.start_level = self.start_level;
+        options.marathon_lines = self.marathon_lines;
+        Ok(())
+    }
+}
+
+/// Reads the settings and keymap in `dir` into `options`. Missing files leave
+/// the defaults alone
+pub fn load(dir: &Path, options: &mut Options) -> Result<(), String> {
+    let path = dir.join(SETTINGS_FILE);
+    if let Some(text) = read(&path)? {
+        toml::from_str::<Settings>(&text)
+            .map_err(|e| e.to_string())
+            .and_then(|settings| settings.apply(options))
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    let path = dir.join(KEYS_FILE);
+    if let Some(text) = read(&path)? {
+        options.keymap = Keymap::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+/// Writes `options` back to the files `load` reads
+pub fn save(dir: &Path, options: &Options) -> Result<(), String> {
+    let settings = toml::to_string(&Settings::from_options(options)).map_err(|e| e.to_string())?;
+    write_atomic(&dir.join(SETTINGS_FILE), &settings)?;
+    write_atomic(&dir.join(KEYS_FILE), &options.keymap.to_toml())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A fresh directory for each test to write config into
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tetris-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_ms_and_frames_round_trip() {
+        for frames in 0..60 {
+            assert_eq!(ms_to_frames(frames_to_ms(frames)), frames);
+        }
+        assert_eq!(ms_to_frames(100), 6);
+        assert_eq!(ms_to_frames(u32::MAX), 257_698_038);
+        assert_eq!(frames_to_ms(u32::MAX), u32::MAX);
+    }
+
+    #[test]
+    fn test_save_then_load() {
+        let dir = temp_dir("save");
+        let mut options = Options::default();
+        options.game.handling.das = 7;
+        options.game.preview_count = 2;
+        options.ghost = false;
+        options.keymap = Keymap::from_preset(crate::keymap::Preset::Wasd);
+        save(&dir, &options).unwrap();
+        assert!(!dir.join("settings.toml.tmp").exists());
+
+        let mut loaded = Options::default();
+        load(&dir, &mut loaded).unwrap();
+        assert_eq!(loaded.game.handling, options.game.handling);
+        assert_eq!(loaded.game.preview_count, 2);
+        assert!(!loaded.ghost);
+        assert_eq!(loaded.keymap, options.keymap);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_bad_settings_are_reported() {
+        let dir = temp_dir("bad");
+        write_atomic(&dir.join(SETTINGS_FILE), "preview = 9\n").unwrap();
+        let error = load(&dir, &mut Options::default()).unwrap_err();
+        assert!(error.contains("preview must be 1 to 6"), "{}", error);
+        write_atomic(&dir.join(SETTINGS_FILE), "sprint_lines = 30\n").unwrap();
+        assert!(load(&dir, &mut Options::default()).is_err());
+        write_atomic(&dir.join(SETTINGS_FILE), "das = 100000000\n").unwrap();
+        let error = load(&dir, &mut Options::default()).unwrap_err();
+        assert!(error.contains("das must be 0 to 333 ms"), "{}", error);
+        write_atomic(&dir.join(SETTINGS_FILE), "sdf = 1000\n").unwrap();
+        let error = load(&dir, &mut Options::default()).unwrap_err();
+        assert!(error.contains("sdf must be 1 to 40"), "{}", error);
+        write_atomic(&dir.join(SETTINGS_FILE), "speed = 9\n").unwrap();
+        assert!(load(&dir, &mut Options::default()).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_missing_files_keep_defaults() {
+        let mut options = Options::default();
+        load(&temp_dir("missing"), &mut options).unwrap();
+        assert_eq!(options.keymap, Keymap::default());
+        assert!(options.ghost);
+    }
+}
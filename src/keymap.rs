@@ -0,0 +1,496 @@
+use std::collections::BTreeMap;
+
+use crossterm::event::{KeyCode, KeyModifiers};
+use serde::{Deserialize, Serialize};
+
+use tetris_core::tetromino::MoveDirection;
+use tetris_core::Command;
+
+/// Everything a key can be bound to while playing
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Quit,
+    Pause,
+    ToggleGhost,
+    Play(Command),
+}
+impl Action {
+    pub const ALL: [Action; 12] = [
+        Action::Play(Command::Move(MoveDirection::Left)),
+        Action::Play(Command::Move(MoveDirection::Right)),
+        Action::Play(Command::Move(MoveDirection::Down)),
+        Action::Play(Command::Move(MoveDirection::SonicDrop)),
+        Action::Play(Command::HardDrop),
+        Action::Play(Command::Move(MoveDirection::CW)),
+        Action::Play(Command::Move(MoveDirection::CCW)),
+        Action::Play(Command::Hold),
+        Action::Pause,
+        Action::ToggleGhost,
+        Action::Quit,
+        Action::Play(Command::Lock),
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Pause => "pause",
+            Action::ToggleGhost => "toggle_ghost",
+            Action::Play(Command::Move(MoveDirection::Left)) => "left",
+            Action::Play(Command::Move(MoveDirection::Right)) => "right",
+            Action::Play(Command::Move(MoveDirection::Down)) => "soft_drop",
+            Action::Play(Command::Move(MoveDirection::SonicDrop)) => "sonic_drop",
+            Action::Play(Command::Move(MoveDirection::CW)) => "rotate_cw",
+            Action::Play(Command::Move(MoveDirection::CCW)) => "rotate_ccw",
+            Action::Play(Command::HardDrop) => "hard_drop",
+            Action::Play(Command::Hold) => "hold",
+            Action::Play(Command::Lock) => "debug_lock",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|action| action.name() == name)
+    }
+}
+
+/// A key plus the modifiers held with it. Shift is folded into the case of
+/// letters, so `H` and `shift+h` are the same key
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Key {
+    code: KeyCodeOrd,
+    modifiers: u8,
+}
+
+// KeyCode doesn't implement Ord, and keys are kept sorted for stable output
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum KeyCodeOrd {
+    Char(char),
+    F(u8),
+    Named(u8),
+}
+
+const NAMED_KEYS: [(&str, KeyCode); 15] = [
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("space", KeyCode::Char(' ')),
+    ("enter", KeyCode::Enter),
+    ("esc", KeyCode::Esc),
+    ("tab", KeyCode::Tab),
+    ("backspace", KeyCode::Backspace),
+    ("insert", KeyCode::Insert),
+    ("delete", KeyCode::Delete),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("pageup", KeyCode::PageUp),
+    ("pagedown", KeyCode::PageDown),
+];
+
+impl Key {
+    /// Normalizes a key as crossterm reports it. None for keys that can't be
+    /// bound, like bare modifiers
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Option<Key> {
+        let modifiers = modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT);
+        let code = match NAMED_KEYS.iter().position(|(_, named)| *named == code) {
+            Some(index) => KeyCodeOrd::Named(index as u8),
+            None => match code {
+                KeyCode::Char(c) => KeyCodeOrd::Char(c),
+                KeyCode::F(n) => KeyCodeOrd::F(n),
+                _ => return None,
+            },
+        };
+        Some(Key {
+            code,
+            modifiers: modifiers.bits(),
+        })
+    }
+
+    /// As `new`, but with shift applied to letters
+    pub fn from_event(code: KeyCode, modifiers: KeyModifiers) -> Option<Key> {
+        let code = match code {
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::SHIFT) => {
+                KeyCode::Char(c.to_ascii_uppercase())
+            }
+            code => code,
+        };
+        Key::new(code, modifiers)
+    }
+
+    pub fn code(self) -> KeyCode {
+        match self.code {
+            KeyCodeOrd::Char(c) => KeyCode::Char(c),
+            KeyCodeOrd::F(n) => KeyCode::F(n),
+            KeyCodeOrd::Named(index) => NAMED_KEYS[index as usize].1,
+        }
+    }
+
+    /// Parses keys like `h`, `H`, `shift+h`, `ctrl+c`, `left`, `space` or `f1`
+    pub fn parse(spec: &str) -> Result<Key, String> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut parts: Vec<&str> = spec.split('+').collect();
+        // A lone or trailing "+" is the plus key itself
+        if spec.ends_with('+') {
+            parts.truncate(parts.len() - 2);
+            parts.push("+");
+        }
+        let Some((name, prefixes)) = parts.split_last() else {
+            return Err(format!("empty key '{}'", spec));
+        };
+        for prefix in prefixes {
+            modifiers |= match prefix.to_ascii_lowercase().as_str() {
+                "shift" => KeyModifiers::SHIFT,
+                "ctrl" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                _ => return Err(format!("unknown modifier '{}' in key '{}'", prefix, spec)),
+            };
+        }
+        let mut chars = name.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => {
+                let lower = name.to_ascii_lowercase();
+                let function = lower
+                    .strip_prefix('f')
+                    .and_then(|n| n.parse().ok())
+                    .filter(|n| (1..=12).contains(n));
+                match function {
+                    Some(n) => KeyCode::F(n),
+                    None => NAMED_KEYS
+                        .iter()
+                        .find(|(named, _)| *named == lower)
+                        .map(|(_, code)| *code)
+                        .ok_or(format!("unknown key '{}'", spec))?,
+                }
+            }
+        };
+        Key::from_event(code, modifiers).ok_or(format!("unknown key '{}'", spec))
+    }
+}
+
+impl std::fmt::Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let modifiers = KeyModifiers::from_bits_truncate(self.modifiers);
+        if modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "ctrl+")?;
+        }
+        if modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "alt+")?;
+        }
+        match self.code {
+            KeyCodeOrd::Char(c) => write!(f, "{}", c),
+            KeyCodeOrd::F(n) => write!(f, "f{}", n),
+            KeyCodeOrd::Named(index) => write!(f, "{}", NAMED_KEYS[index as usize].0),
+        }
+    }
+}
+
+/// Starting points for a keymap
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Preset {
+    /// hjkl, the original bindings
+    #[default]
+    Vim,
+    Arrows,
+    Wasd,
+}
+impl Preset {
+    pub const ALL: [Preset; 3] = [Preset::Vim, Preset::Arrows, Preset::Wasd];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::Vim => "vim",
+            Preset::Arrows => "arrows",
+            Preset::Wasd => "wasd",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Preset> {
+        Preset::ALL.into_iter().find(|preset| preset.name() == name)
+    }
+
+    fn bindings(self) -> &'static [(&'static str, &'static [&'static str])] {
+        match self {
+            Preset::Vim => &[
+                ("left", &["h"]),
+                ("right", &["l"]),
+                ("soft_drop", &["j"]),
+                ("sonic_drop", &["J"]),
+                ("hard_drop", &["space"]),
+                ("rotate_cw", &["L"]),
+                ("rotate_ccw", &["H"]),
+                ("hold", &["c"]),
+                ("pause", &["esc", "p"]),
+                ("toggle_ghost", &["g"]),
+                ("quit", &["q"]),
+                ("debug_lock", &["d"]),
+            ],
+            Preset::Arrows => &[
+                ("left", &["left"]),
+                ("right", &["right"]),
+                ("soft_drop", &["down"]),
+                ("hard_drop", &["space"]),
+                ("rotate_cw", &["up", "x"]),
+                ("rotate_ccw", &["z"]),
+                ("hold", &["c"]),
+                ("pause", &["esc", "p"]),
+                ("toggle_ghost", &["g"]),
+                ("quit", &["q"]),
+            ],
+            Preset::Wasd => &[
+                ("left", &["a"]),
+                ("right", &["d"]),
+                ("soft_drop", &["s"]),
+                ("hard_drop", &["w", "space"]),
+                ("rotate_cw", &["k"]),
+                ("rotate_ccw", &["j"]),
+                ("hold", &["l"]),
+                ("pause", &["esc", "p"]),
+                ("toggle_ghost", &["g"]),
+                ("quit", &["q"]),
+            ],
+        }
+    }
+}
+
+// The config file as written
+#[derive(Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct KeymapFile {
+    preset: Option<String>,
+    #[serde(default)]
+    keys: BTreeMap<String, Vec<String>>,
+}
+
+/// Which keys do what. Each action can have any number of keys, each key
+/// does at most one thing
+#[derive(Clone, Debug, PartialEq)]
+pub struct Keymap {
+    preset: Preset,
+    bindings: BTreeMap<Key, Action>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::from_preset(Preset::default())
+    }
+}
+
+impl Keymap {
+    pub fn from_preset(preset: Preset) -> Keymap {
+        Keymap::build(preset, &BTreeMap::new()).expect("presets are valid")
+    }
+
+    // Binds the preset, replacing its keys for every action listed in
+    // `overrides`. Every problem is reported, not just the first
+    fn build(preset: Preset, overrides: &BTreeMap<String, Vec<String>>) -> Result<Keymap, String> {
+        let mut errors = Vec::new();
+        let mut keys_by_action: Vec<(Action, Vec<String>)> = Vec::new();
+        for (name, keys) in preset.bindings() {
+            let action = Action::from_name(name).expect("preset action names are valid");
+            keys_by_action.push((action, keys.iter().map(|k| k.to_string()).collect()));
+        }
+        for (name, keys) in overrides {
+            let Some(action) = Action::from_name(name) else {
+                errors.push(format!("unknown action '{}'", name));
+                continue;
+            };
+            keys_by_action.retain(|(bound, _)| *bound != action);
+            keys_by_action.push((action, keys.clone()));
+        }
+
+        let mut bindings = BTreeMap::new();
+        for (action, keys) in keys_by_action {
+            for spec in keys {
+                let key = match Key::parse(&spec) {
+                    Ok(key) => key,
+                    Err(error) => {
+                        errors.push(error);
+                        continue;
+                    }
+                };
+                match bindings.insert(key, action) {
+                    Some(other) if other != action => errors.push(format!(
+                        "'{}' is bound to both {} and {}",
+                        key,
+                        other.name(),
+                        action.name()
+                    )),
+                    _ => {}
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(Keymap { preset, bindings })
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+
+    /// Parses a keymap config file. Actions listed under `[keys]` replace
+    /// the preset's keys for that action, the rest keep the preset's
+    ///
+    /// ```toml
+    /// preset = "arrows"
+    ///
+    /// [keys]
+    /// hard_drop = ["space", "enter"]
+    /// hold = ["c", "ctrl+h"]
+    /// ```
+    pub fn parse(text: &str) -> Result<Keymap, String> {
+        let file: KeymapFile = toml::from_str(text).map_err(|e| e.to_string())?;
+        let preset = match file.preset {
+            Some(name) => Preset::from_name(&name).ok_or(format!("unknown preset '{}'", name))?,
+            None => Preset::default(),
+        };
+        Keymap::build(preset, &file.keys)
+    }
+
+    /// Writes the keymap out in the form `parse` reads. Only actions whose
+    /// keys differ from the preset's are listed
+    pub fn to_toml(&self) -> String {
+        let preset = Keymap::from_preset(self.preset);
+        let keys = Action::ALL
+            .into_iter()
+            .filter(|action| !self.keys(*action).eq(preset.keys(*action)))
+            .map(|action| {
+                let keys = self.keys(action).map(|key| key.to_string()).collect();
+                (action.name().to_string(), keys)
+            })
+            .collect();
+        let file = KeymapFile {
+            preset: Some(self.preset.name().to_string()),
+            keys,
+        };
+        toml::to_string(&file).expect("keymaps always serialize")
+    }
+
+    pub fn preset(&self) -> Preset {
+        self.preset
+    }
+
+    pub fn action(&self, key: Key) -> Option<Action> {
+        self.bindings.get(&key).copied()
+    }
+
+    /// Binds `key` to `action`, taking it from whatever it did before. If it
+    /// was already bound to `action` it is unbound instead
+    pub fn toggle(&mut self, key: Key, action: Action) {
+        if self.bindings.get(&key) == Some(&action) {
+            self.bindings.remove(&key);
+        } else {
+            self.bindings.insert(key, action);
+        }
+    }
+
+    /// Every key bound to `action`
+    pub fn keys(&self, action: Action) -> impl Iterator<Item = Key> + '_ {
+        self.bindings
+            .iter()
+            .filter(move |(_, bound)| **bound == action)
+            .map(|(key, _)| *key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_keys() {
+        let key = |spec| Key::parse(spec).unwrap();
+        assert_eq!(key("H"), key("shift+h"));
+        assert_ne!(key("h"), key("H"));
+        assert_eq!(key("space").code(), KeyCode::Char(' '));
+        assert_eq!(key("Left").code(), KeyCode::Left);
+        assert_eq!(key("f5").code(), KeyCode::F(5));
+        assert_eq!(key("+").code(), KeyCode::Char('+'));
+        assert_eq!(key("ctrl++").to_string(), "ctrl++");
+        assert_eq!(key("ctrl+c").to_string(), "ctrl+c");
+        assert!(Key::parse("hyper+h").is_err());
+        assert!(Key::parse("nope").is_err());
+    }
+
+    #[test]
+    fn test_event_keys_match_parsed_keys() {
+        let shifted = Key::from_event(KeyCode::Char('h'), KeyModifiers::SHIFT);
+        assert_eq!(shifted, Key::parse("H").ok());
+        let plain = Key::from_event(KeyCode::Char('h'), KeyModifiers::NONE);
+        assert_eq!(plain, Key::parse("h").ok());
+        let ctrl = Key::from_event(KeyCode::Char('c'), KeyModifiers::CONTROL);
+        assert_eq!(ctrl, Key::parse("ctrl+c").ok());
+    }
+
+    #[test]
+    fn test_presets_are_valid() {
+        for preset in Preset::ALL {
+            let keymap = Keymap::from_preset(preset);
+            assert_eq!(Preset::from_name(preset.name()), Some(preset));
+            let hard_drop = Action::Play(Command::HardDrop);
+            assert!(keymap.keys(hard_drop).count() > 0);
+        }
+        let vim = Keymap::default();
+        assert_eq!(
+            vim.action(Key::parse("J").unwrap()),
+            Some(Action::Play(Command::Move(MoveDirection::SonicDrop)))
+        );
+    }
+
+    #[test]
+    fn test_config_overrides_preset_per_action() {
+        let keymap = Keymap::parse(
+            r#"
+            preset = "arrows"
+
+            [keys]
+            hard_drop = ["up"]
+            rotate_cw = ["x", "ctrl+x"]
+            "#,
+        )
+        .unwrap();
+        let up = Key::parse("up").unwrap();
+        assert_eq!(keymap.action(up), Some(Action::Play(Command::HardDrop)));
+        assert_eq!(keymap.action(Key::parse("space").unwrap()), None);
+        let rotate_cw = Action::Play(Command::Move(MoveDirection::CW));
+        assert_eq!(keymap.keys(rotate_cw).count(), 2);
+        assert_eq!(
+            keymap.action(Key::parse("left").unwrap()),
+            Some(Action::Play(Command::Move(MoveDirection::Left)))
+        );
+    }
+
+    #[test]
+    fn test_toggle_and_write_back() {
+        let mut keymap = Keymap::from_preset(Preset::Arrows);
+        let hold = Action::Play(Command::Hold);
+        let space = Key::parse("space").unwrap();
+        keymap.toggle(space, hold);
+        assert_eq!(keymap.action(space), Some(hold));
+        keymap.toggle(Key::parse("c").unwrap(), hold);
+        assert_eq!(keymap.keys(hold).collect::<Vec<_>>(), [space]);
+
+        let text = keymap.to_toml();
+        assert!(text.contains("hold = [\"space\"]"), "{}", text);
+        assert!(text.contains("hard_drop = []"), "{}", text);
+        assert!(!text.contains("left"), "{}", text);
+        assert_eq!(Keymap::parse(&text).unwrap(), keymap);
+    }
+
+    #[test]
+    fn test_conflicts_and_mistakes_are_all_reported() {
+        let error = Keymap::parse(
+            r#"
+            [keys]
+            hold = ["h"]
+            spin = ["s"]
+            quit = ["shift+meta+q"]
+            "#,
+        )
+        .unwrap_err();
+        assert!(error.contains("'h' is bound to both"), "{}", error);
+        assert!(error.contains("unknown action 'spin'"), "{}", error);
+        assert!(error.contains("unknown modifier 'meta'"), "{}", error);
+        assert!(Keymap::parse("preset = \"dvorak\"").is_err());
+        assert!(Keymap::parse("bogus = 1").is_err());
+    }
+}
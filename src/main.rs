@@ -7,18 +7,44 @@ use ratatui::widgets;
 
 use itertools::Itertools;
 
-mod utils;
-use utils::Vec2;
+use std::collections::VecDeque;
 
-mod tetromino;
-use tetromino::Colors;
-use tetromino::MoveDirection;
-use tetromino::Tetromino;
-use tetromino::TetrominoRotation;
-use tetromino::TetrominoShape;
+use tetris_core::gravity;
+use tetris_core::gravity::GravityCurve;
+use tetris_core::lock_delay::LockResetPolicy;
+use tetris_core::randomizer::RandomizerKind;
+use tetris_core::tetromino::Colors;
+use tetris_core::tetromino::Tetromino;
+use tetris_core::tetromino::TetrominoRotation;
+use tetris_core::tetromino::TetrominoShape;
+use tetris_core::Vec2;
+use tetris_core::{
+    Command, Event, Game, GameBoard, GameOptions, GameState, Goal, Input, GAME_SIZE,
+};
 
+#[allow(dead_code)]
 mod space_partition;
 
+mod config;
+
+mod keymap;
+use keymap::Action;
+use keymap::Key;
+use keymap::Keymap;
+
+mod marathon;
+use marathon::LevelLog;
+
+mod menu;
+use menu::Menu;
+use menu::MenuKey;
+
+mod records;
+use records::Records;
+
+mod sprint;
+use sprint::Splits;
+
 type Terminal = tui::Terminal<tui::CrosstermBackend<std::io::Stdout>>;
 
 // fn gen_2d_range(from: usize, to: usize) -> impl Iterator<Item = (usize, usize)> {
@@ -27,41 +53,41 @@ type Terminal = tui::Terminal<tui::CrosstermBackend<std::io::Stdout>>;
 
 const TILE_SIZE: Vec2<usize> = Vec2 { x: 3, y: 2 };
 const TILE_CHARS: [[char; TILE_SIZE.x]; TILE_SIZE.y] = [['┌', '─', '┐'], ['└', '─', '┘']];
+const GHOST_CHARS: [[char; TILE_SIZE.x]; TILE_SIZE.y] = [['╭', '┄', '╮'], ['╰', '┄', '╯']];
 // const TILE_CHARS: [[char; TILE_SIZE.x]; TILE_SIZE.y] = [['▛', '▀', '▜'], ['▙', '▅', '▟']];
 // const TILE_CHARS: [[char; TILE_SIZE.x]; TILE_SIZE.y] = [['▉', '▉', '▉'], ['▉', '▉', '▉']];
 
-const GAME_SIZE: Vec2<usize> = Vec2 { x: 10, y: 22 };
+// Size of the board on screen
+const BOARD_CHARS: Vec2<usize> = Vec2 {
+    x: TILE_SIZE.x * GAME_SIZE.x,
+    y: TILE_SIZE.y * GAME_SIZE.y,
+};
 
-#[derive(Clone, Copy)]
-struct ActivePiece {
-    pos: Vec2<usize>,
-    tetromino: Tetromino,
+fn tui_color(color: Colors) -> tui::Color {
+    match color {
+        Colors::Purple => tui::Color::Rgb(155, 89, 182),
+        Colors::Orange => tui::Color::Rgb(242, 140, 40),
+        Colors::Pink => tui::Color::Rgb(241, 148, 138),
+        Colors::Red => tui::Color::Red,
+        Colors::Green => tui::Color::Green,
+        Colors::Blue => tui::Color::Cyan,
+        Colors::Yellow => tui::Color::Yellow,
+    }
 }
 
+// (grid_y, grid_x), (char_y, char_x), (tile_y, tile_x), (buf_y, buf_x)
+type GridTileBufItem = ((usize, usize), (usize, usize), (usize, usize), (u16, u16));
+
+/// The board as drawn in the terminal
 #[derive(Clone, Copy)]
-struct GameBoard {
-    grid: [[Option<Colors>; GAME_SIZE.x]; GAME_SIZE.y],
-    grid_dims_chars: Vec2<usize>,
-    active_peice: Option<ActivePiece>,
+struct BoardView<'a> {
+    board: &'a GameBoard,
+    show_ghost: bool,
 }
-impl GameBoard {
-    fn new() -> Self {
-        Self {
-            grid: [[None; GAME_SIZE.x]; GAME_SIZE.y],
-            grid_dims_chars: Vec2::<usize>::new(
-                TILE_SIZE.x * GAME_SIZE.x,
-                TILE_SIZE.y * GAME_SIZE.y,
-            ),
-            active_peice: None,
-        }
-    }
-    fn grid_tile_buf_iterator(
-        &self,
-        area: tui::Rect,
-    ) -> impl Iterator<Item = ((usize, usize), (usize, usize), (usize, usize), (u16, u16))> + '_
-    {
-        (0..self.grid_dims_chars.y).flat_map(move |char_y| {
-            (0..self.grid_dims_chars.x).map(move |char_x| {
+impl BoardView<'_> {
+    fn grid_tile_buf_iterator(area: tui::Rect) -> impl Iterator<Item = GridTileBufItem> {
+        (0..BOARD_CHARS.y).flat_map(move |char_y| {
+            (0..BOARD_CHARS.x).map(move |char_x| {
                 (
                     (char_y / TILE_SIZE.y, char_x / TILE_SIZE.x),
                     (char_y, char_x),
@@ -71,232 +97,763 @@ impl GameBoard {
             })
         })
     }
-    fn draw_to_board(&mut self, pos: Vec2<usize>, tetromino: Tetromino) {
-        for (_grid_y, _grid_x) in tetromino.blocks.iter() {
-            let (grid_y, grid_x) = (_grid_y + pos.y, _grid_x + pos.x);
-            self.grid[grid_y][grid_x] = Some(tetromino.color);
+
+    fn render_piece(
+        pos: Vec2<i32>,
+        tetromino: Tetromino,
+        chars: &[[char; TILE_SIZE.x]; TILE_SIZE.y],
+        style: tui::Style,
+        area: tui::Rect,
+        buf: &mut tui::Buffer,
+    ) {
+        for (grid_y, grid_x) in tetromino.blocks.iter() {
+            for (tile_y, tile_x) in (0..TILE_SIZE.y).cartesian_product(0..TILE_SIZE.x) {
+                let (pos_on_grid_y, pos_on_grid_x) = (
+                    (*grid_y as i32 + pos.y) as usize,
+                    (*grid_x as i32 + pos.x) as usize,
+                );
+                let (char_y, char_x) = (pos_on_grid_y * TILE_SIZE.y, pos_on_grid_x * TILE_SIZE.x);
+                let (buf_y, buf_x) = (
+                    area.top() + (char_y + tile_y) as u16,
+                    area.left() + (char_x + tile_x) as u16,
+                );
+
+                let character = chars[tile_y][tile_x];
+
+                buf.get_mut(buf_x, buf_y)
+                    .set_symbol(&character.to_string())
+                    .set_style(style);
+            }
         }
     }
 }
 
-impl<'a> widgets::Widget for GameBoard {
+impl widgets::Widget for BoardView<'_> {
     fn render(self, area: tui::Rect, buf: &mut tui::Buffer) {
-        for ((grid_y, grid_x), (char_y, char_x), (tile_y, tile_x), (buf_y, buf_x)) in
-            self.grid_tile_buf_iterator(area)
+        for ((grid_y, grid_x), _, (tile_y, tile_x), (buf_y, buf_x)) in
+            Self::grid_tile_buf_iterator(area)
         {
-            match self.grid[grid_y][grid_x] {
-                Some(monomino) => {
-                    let character = TILE_CHARS[tile_y][tile_x];
-                    let color = monomino.to_tui_color();
-                    buf.get_mut(buf_x, buf_y)
-                        .set_symbol(&character.to_string())
-                        .set_fg(color);
-                }
-                None => {}
-            };
+            if let Some(monomino) = self.board.grid[grid_y][grid_x] {
+                let character = TILE_CHARS[tile_y][tile_x];
+                let color = tui_color(monomino);
+                buf.get_mut(buf_x, buf_y)
+                    .set_symbol(&character.to_string())
+                    .set_fg(color);
+            }
         }
-        if let Some(ap) = self.active_peice {
-            for (grid_y, grid_x) in ap.tetromino.blocks.iter() {
-                for (tile_y, tile_x) in (0..TILE_SIZE.y).cartesian_product(0..TILE_SIZE.x) {
-                    let (pos_on_grid_y, pos_on_grid_x) = (grid_y + ap.pos.y, grid_x + ap.pos.x);
-                    let (char_y, char_x) =
-                        (pos_on_grid_y * TILE_SIZE.y, pos_on_grid_x * TILE_SIZE.x);
-                    let (buf_y, buf_x) = (
-                        area.top() + (char_y + tile_y) as u16,
-                        area.left() + (char_x + tile_x) as u16,
-                    );
-
-                    let character = TILE_CHARS[tile_y][tile_x];
-
-                    buf.get_mut(buf_x, buf_y)
-                        .set_symbol(&character.to_string())
-                        .set_fg(ap.tetromino.color.to_tui_color());
-                }
+        if let Some(ap) = self.board.active_peice {
+            let color = tui_color(ap.tetromino.color);
+            if self.show_ghost {
+                let ghost_pos =
+                    ap.pos + Vec2::new(0, self.board.drop_distance(ap.pos, ap.tetromino));
+                let style = tui::Style::default()
+                    .fg(color)
+                    .add_modifier(tui::Modifier::DIM);
+                Self::render_piece(ghost_pos, ap.tetromino, &GHOST_CHARS, style, area, buf);
+            }
+            let style = tui::Style::default().fg(color);
+            Self::render_piece(ap.pos, ap.tetromino, &TILE_CHARS, style, area, buf);
+        }
+    }
+}
+
+/// Draws a lone tetromino in `area`, trimmed to its blocks and centred
+/// horizontally. Used for the side panels, not the board
+fn render_tetromino(
+    tetromino: Tetromino,
+    color: tui::Color,
+    area: tui::Rect,
+    buf: &mut tui::Buffer,
+) {
+    let min_y = tetromino.blocks.iter().map(|(y, _)| *y).min().unwrap_or(0);
+    let min_x = tetromino.blocks.iter().map(|(_, x)| *x).min().unwrap_or(0);
+    let max_x = tetromino.blocks.iter().map(|(_, x)| *x).max().unwrap_or(0);
+    let width = ((max_x - min_x + 1) * TILE_SIZE.x) as u16;
+    let left = area.left() + area.width.saturating_sub(width) / 2;
+    for (grid_y, grid_x) in tetromino.blocks.iter() {
+        for (tile_y, tile_x) in (0..TILE_SIZE.y).cartesian_product(0..TILE_SIZE.x) {
+            let (buf_y, buf_x) = (
+                area.top() + ((grid_y - min_y) * TILE_SIZE.y + tile_y) as u16,
+                left + ((grid_x - min_x) * TILE_SIZE.x + tile_x) as u16,
+            );
+            if buf_y >= area.bottom() || buf_x >= area.right() {
+                continue;
+            }
+            buf.get_mut(buf_x, buf_y)
+                .set_symbol(&TILE_CHARS[tile_y][tile_x].to_string())
+                .set_fg(color);
+        }
+    }
+}
+
+/// Formats a number of frames as minutes, seconds and milliseconds
+fn format_time(frames: u64) -> String {
+    let time = Game::frame_time(frames);
+    format!(
+        "{}:{:02}.{:03}",
+        time.as_secs() / 60,
+        time.as_secs() % 60,
+        time.subsec_millis()
+    )
+}
+
+const MAX_PREVIEW: usize = 6;
+/// The time limits an ultra game can have, in seconds
+const ULTRA_SECONDS: [u64; 4] = [60, 120, 180, 300];
+// How far the settings screen lets handling go, in frames
+const MAX_DAS: u32 = 20;
+const MAX_ARR: u32 = 10;
+const MAX_SDF: u32 = 40;
+
+/// The upcoming pieces, soonest first
+struct NextQueue<'a> {
+    shapes: &'a VecDeque<TetrominoShape>,
+}
+
+impl<'a> widgets::Widget for NextQueue<'a> {
+    fn render(self, area: tui::Rect, buf: &mut tui::Buffer) {
+        // Pieces in spawn orientation are at most two tiles tall, plus a gap
+        let slot_height = (3 * TILE_SIZE.y) as u16;
+        for (i, shape) in self.shapes.iter().enumerate() {
+            let top = area.top() + i as u16 * slot_height;
+            if top >= area.bottom() {
+                break;
             }
+            let slot = tui::Rect::new(
+                area.left(),
+                top,
+                area.width,
+                slot_height.min(area.bottom() - top),
+            );
+            let tetromino = Tetromino::new(*shape, TetrominoRotation::Zero);
+            render_tetromino(tetromino, tui_color(tetromino.color), slot, buf);
+        }
+    }
+}
+
+/// Which screen the app is showing. Only `Playing` advances the game
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Screen {
+    Title,
+    ModeSelect,
+    Playing,
+    Paused,
+    GameOver,
+    Settings,
+}
+
+/// The kinds of game on offer
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Mode {
+    Endless,
+    /// Clear a set number of lines as fast as possible
+    Sprint,
+    /// Score as much as possible before time runs out
+    Ultra,
+    /// Climb the levels to a line goal, or for as long as you last
+    Marathon,
+}
+impl Mode {
+    fn name(self) -> &'static str {
+        match self {
+            Mode::Endless => "endless",
+            Mode::Sprint => "sprint",
+            Mode::Ultra => "ultra",
+            Mode::Marathon => "marathon",
         }
     }
 }
 
-enum Action {
+/// An entry in one of the menus
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Choice {
+    Play,
+    Settings,
     Quit,
-    DebugDrawCurrentPiece,
-    MovePiece(MoveDirection),
+    Endless,
+    Sprint,
+    Ultra,
+    Marathon,
+    Resume,
+    Restart,
+    Title,
+    Back,
+    Setting(Setting),
+}
+impl Choice {
+    fn label(self) -> &'static str {
+        match self {
+            Choice::Play => "Play",
+            Choice::Settings => "Settings",
+            Choice::Quit => "Quit",
+            Choice::Endless => "Endless",
+            Choice::Sprint => "Sprint",
+            Choice::Ultra => "Ultra",
+            Choice::Marathon => "Marathon",
+            Choice::Resume => "Resume",
+            Choice::Restart => "Restart",
+            Choice::Title => "Back to title",
+            Choice::Back => "Back",
+            Choice::Setting(Setting::Preset) => "Keys",
+            Choice::Setting(Setting::Das) => "DAS",
+            Choice::Setting(Setting::Arr) => "ARR",
+            Choice::Setting(Setting::Sdf) => "SDF",
+            Choice::Setting(Setting::Preview) => "Preview",
+            Choice::Setting(Setting::Ghost) => "Ghost",
+            Choice::Setting(Setting::SprintLines) => "Sprint goal",
+            Choice::Setting(Setting::UltraSeconds) => "Ultra time",
+            Choice::Setting(Setting::StartLevel) => "Start level",
+            Choice::Setting(Setting::MarathonLines) => "Marathon goal",
+            Choice::Setting(Setting::Bind(action)) => action.name(),
+        }
+    }
+}
+
+/// A row of the settings screen. Left and right change its value
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Setting {
+    Preset,
+    Das,
+    Arr,
+    Sdf,
+    Preview,
+    Ghost,
+    SprintLines,
+    UltraSeconds,
+    StartLevel,
+    MarathonLines,
+    Bind(Action),
+}
+
+// The item `delta` places along from `current`, stopping at either end
+fn step_through<T: Copy + PartialEq>(items: &[T], current: T, delta: i32) -> T {
+    let index = items.iter().position(|item| *item == current).unwrap_or(0);
+    let index = index.saturating_add_signed(delta as isize);
+    items[index.min(items.len() - 1)]
+}
+
+// Draws `value` as a bar filled out of `max`
+fn slider(value: u32, max: u32) -> String {
+    const WIDTH: u32 = 10;
+    let filled = (value.min(max) * WIDTH + max / 2) / max;
+    format!(
+        "[{}{}]",
+        "■".repeat(filled as usize),
+        "·".repeat((WIDTH - filled) as usize)
+    )
+}
+
+// Choices that carry over from one game to the next
+#[derive(Clone)]
+struct Options {
+    game: GameOptions,
+    ghost: bool,
+    keymap: Keymap,
+    sprint_lines: usize,
+    ultra_seconds: u64,
+    // Zero for no goal
+    marathon_lines: usize,
+}
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            game: GameOptions::default(),
+            ghost: true,
+            keymap: Keymap::default(),
+            sprint_lines: 40,
+            ultra_seconds: 120,
+            marathon_lines: 150,
+        }
+    }
 }
 
 struct App {
-    game_board: GameBoard,
+    // The saved settings with this run's command line flags over them
+    options: Options,
+    // The settings as they are on disk. Only what the player changes in the
+    // menus is copied over, so flags are never saved
+    saved: Options,
+    screen: Screen,
+    mode: Mode,
+    // Index into the current screen's menu
+    selected: usize,
+    game: Game,
+    // Only kept in sprint mode
+    splits: Option<Splits>,
+    // Only kept in marathon mode
+    level_log: Option<LevelLog>,
+    records: Records,
+    // Where records are saved. None keeps them for this run only
+    data_dir: Option<std::path::PathBuf>,
+    // Shown with the results, like a new best
+    result_note: Option<String>,
+    // Inputs waiting for the next game step. Releases that happen while the
+    // game is paused wait here too, so keys don't stay held after resuming
+    inputs: Vec<Input>,
+    // Whether the terminal reports key releases. Until it does we fall back on
+    // the OS key repeat and treat every press as a tap
+    keyboard_enhanced: bool,
+    // The action on the settings screen waiting for a key to bind to it
+    capturing: Option<Action>,
+    // Where settings are saved when leaving settings or mode select. None keeps
+    // them for this run only
+    config_dir: Option<std::path::PathBuf>,
+    settings_error: Option<String>,
     should_quit: bool,
     debug_text: String,
 }
 
 impl App {
-    fn new() -> App {
+    fn new(options: Options) -> App {
         App {
-            game_board: GameBoard::new(),
+            game: Game::new(options.game.clone()),
+            saved: options.clone(),
+            options,
+            screen: Screen::Title,
+            mode: Mode::Endless,
+            selected: 0,
+            splits: None,
+            level_log: None,
+            records: Records::default(),
+            data_dir: None,
+            result_note: None,
+            inputs: Vec::new(),
+            keyboard_enhanced: false,
+            capturing: None,
+            config_dir: None,
+            settings_error: None,
             should_quit: false,
             debug_text: String::from("Hello Wold\n"),
         }
     }
 
-    fn get_user_input(&self) -> Result<Option<Action>> {
-        use crossterm::event as c_event;
+    fn enter(&mut self, screen: Screen) {
+        self.screen = screen;
+        self.selected = 0;
+    }
 
-        let timeout = std::time::Duration::from_millis(100);
-        if c_event::poll(timeout)? {
-            if let c_event::Event::Key(key) = c_event::read()? {
-                if key.kind == c_event::KeyEventKind::Press {
-                    return Ok(match key.code {
-                        c_event::KeyCode::Char('q') => Some(Action::Quit),
-                        c_event::KeyCode::Char('d') => Some(Action::DebugDrawCurrentPiece),
-                        c_event::KeyCode::Char('h') => Some(Action::MovePiece(MoveDirection::Left)),
-                        c_event::KeyCode::Char('j') => Some(Action::MovePiece(MoveDirection::Down)),
-                        c_event::KeyCode::Char('l') => {
-                            Some(Action::MovePiece(MoveDirection::Right))
-                        }
-                        c_event::KeyCode::Char('H') => Some(Action::MovePiece(MoveDirection::CCW)),
-                        c_event::KeyCode::Char('L') => Some(Action::MovePiece(MoveDirection::CW)),
-                        _ => None,
-                    });
+    fn ultra_frames(&self) -> u64 {
+        self.options.ultra_seconds * u64::from(gravity::FRAME_RATE)
+    }
+
+    fn start_game(&mut self, mode: Mode) {
+        // Goals picked on mode select are kept for next time. Not being able
+        // to save them shouldn't stop the game, the error shows on mode
+        // select afterwards
+        if self.screen == Screen::ModeSelect {
+            self.save_settings();
+        }
+        let mut options = self.options.game.clone();
+        // The chosen start level lives in the game options, but only marathon
+        // plays it. Every other mode starts on level 1
+        let start_level = std::mem::replace(&mut options.start_level, 1);
+        self.splits = None;
+        self.level_log = None;
+        options.goal = match mode {
+            Mode::Endless => Goal::Endless,
+            Mode::Sprint => {
+                let lines = self.options.sprint_lines;
+                self.splits = Some(Splits::new(lines, self.records.sprint_best(lines)));
+                Goal::Lines(lines)
+            }
+            Mode::Ultra => Goal::Frames(self.ultra_frames()),
+            Mode::Marathon => {
+                options.start_level = start_level;
+                options.lines_per_level = marathon::LINES_PER_LEVEL;
+                self.level_log = Some(LevelLog::new(start_level));
+                match self.options.marathon_lines {
+                    0 => Goal::Endless,
+                    lines => Goal::Lines(lines),
                 }
             }
+        };
+        self.mode = mode;
+        self.game = Game::new(options);
+        self.result_note = None;
+        self.inputs.clear();
+        self.enter(Screen::Playing);
+    }
+
+    fn menu_choices(&self) -> Vec<Choice> {
+        match self.screen {
+            Screen::Title => vec![Choice::Play, Choice::Settings, Choice::Quit],
+            Screen::ModeSelect => vec![
+                Choice::Endless,
+                Choice::Sprint,
+                Choice::Setting(Setting::SprintLines),
+                Choice::Ultra,
+                Choice::Setting(Setting::UltraSeconds),
+                Choice::Marathon,
+                Choice::Setting(Setting::StartLevel),
+                Choice::Setting(Setting::MarathonLines),
+                Choice::Back,
+            ],
+            Screen::Playing => vec![],
+            Screen::Paused => vec![Choice::Resume, Choice::Restart, Choice::Title],
+            Screen::GameOver => vec![Choice::Restart, Choice::Title, Choice::Quit],
+            Screen::Settings => [
+                Setting::Preset,
+                Setting::Das,
+                Setting::Arr,
+                Setting::Sdf,
+                Setting::Preview,
+                Setting::Ghost,
+            ]
+            .into_iter()
+            .chain(Action::ALL.map(Setting::Bind))
+            .map(Choice::Setting)
+            .chain([Choice::Back])
+            .collect(),
         }
-        Ok(None) // Return Ok(None) if no event is detected or if the event is not a key press
     }
 
-    // Logic for making the current piece static and making a new one
-    // {
-    //     if let Some(ap) = self.game_board.active_peice {
-    //         self.game_board.draw_to_board(ap.pos, ap.tetromino);
-    //         let new_ap = ActivePiece {
-    //             pos: Vec2::new(3, 0),
-    //             tetromino: TetrominoShape::get_data(
-    //                 TetrominoShape::O,
-    //                 TetrominoRotation::Zero,
-    //             ),
-    //         };
-    //         self.game_board.active_peice = Some(new_ap);
-    //     }
-    // }
+    fn choose(&mut self, choice: Choice) {
+        match choice {
+            Choice::Play => self.enter(Screen::ModeSelect),
+            Choice::Settings => self.enter(Screen::Settings),
+            Choice::Quit => self.should_quit = true,
+            Choice::Endless => self.start_game(Mode::Endless),
+            Choice::Sprint => self.start_game(Mode::Sprint),
+            Choice::Ultra => self.start_game(Mode::Ultra),
+            Choice::Marathon => self.start_game(Mode::Marathon),
+            Choice::Restart => self.start_game(self.mode),
+            Choice::Resume => self.enter(Screen::Playing),
+            Choice::Back if matches!(self.screen, Screen::Settings | Screen::ModeSelect) => {
+                self.leave_settings()
+            }
+            Choice::Title | Choice::Back => self.enter(Screen::Title),
+            Choice::Setting(Setting::Bind(action)) => self.capturing = Some(action),
+            Choice::Setting(setting) => self.adjust(setting, 1),
+        }
+    }
 
-    fn game_loop(&mut self, mut terminal: Terminal) -> Result<()> {
-        let input_rate = std::time::Duration::from_millis(100);
-        let loop_rate = std::time::Duration::from_millis(10); // consistent loop rate
-        let gravity_frame_rate = std::time::Duration::from_millis(1000);
+    fn menu_key(&mut self, key: MenuKey) {
+        let choices = self.menu_choices();
+        match key {
+            MenuKey::Up | MenuKey::Down => {
+                self.selected = menu::navigate(self.selected, choices.len(), key);
+            }
+            MenuKey::Left | MenuKey::Right => {
+                if let Some(Choice::Setting(setting)) = choices.get(self.selected) {
+                    self.adjust(*setting, if key == MenuKey::Left { -1 } else { 1 });
+                }
+            }
+            MenuKey::Select => {
+                if let Some(choice) = choices.get(self.selected) {
+                    self.choose(*choice);
+                }
+            }
+            MenuKey::Back => match self.screen {
+                Screen::Settings | Screen::ModeSelect => self.leave_settings(),
+                Screen::GameOver => self.enter(Screen::Title),
+                Screen::Paused => self.enter(Screen::Playing),
+                Screen::Title | Screen::Playing => {}
+            },
+        }
+    }
 
-        let mut last_input_check = std::time::Instant::now();
-        let mut last_gravity_frame_update = std::time::Instant::now();
-        let mut needs_redraw = true; // flag to track if we need to redraw
+    // Steps a setting up or down, stopping at the ends of its range
+    fn adjust(&mut self, setting: Setting, delta: i32) {
+        let nudge = |value: u32, range: std::ops::RangeInclusive<u32>| {
+            value
+                .saturating_add_signed(delta)
+                .clamp(*range.start(), *range.end())
+        };
+        let options = &mut self.options;
+        let handling = &mut options.game.handling;
+        match setting {
+            Setting::Preset => {
+                let presets = keymap::Preset::ALL;
+                let current = options.keymap.preset();
+                let index = presets.iter().position(|p| *p == current).unwrap() as i32;
+                let next = (index + delta).rem_euclid(presets.len() as i32);
+                options.keymap = Keymap::from_preset(presets[next as usize]);
+            }
+            Setting::Das => handling.das = nudge(handling.das, 0..=MAX_DAS),
+            Setting::Arr => handling.arr = nudge(handling.arr, 0..=MAX_ARR),
+            Setting::Sdf => handling.sdf = nudge(handling.sdf, 1..=MAX_SDF),
+            Setting::Preview => {
+                let count = options.game.preview_count as u32;
+                options.game.preview_count = nudge(count, 1..=MAX_PREVIEW as u32) as usize;
+            }
+            Setting::Ghost => options.ghost = !options.ghost,
+            Setting::SprintLines => {
+                options.sprint_lines =
+                    step_through(&sprint::SPRINT_LINES, options.sprint_lines, delta);
+            }
+            Setting::UltraSeconds => {
+                options.ultra_seconds = step_through(&ULTRA_SECONDS, options.ultra_seconds, delta);
+            }
+            Setting::StartLevel => {
+                let level = options.game.start_level as u32;
+                options.game.start_level =
+                    nudge(level, 1..=marathon::MAX_START_LEVEL as u32) as usize;
+            }
+            Setting::MarathonLines => {
+                options.marathon_lines =
+                    step_through(&marathon::MARATHON_LINES, options.marathon_lines, delta);
+            }
+            Setting::Bind(_) => {}
+        }
+        self.keep(setting);
+    }
 
-        loop {
-            let loop_start = std::time::Instant::now();
-            // Input handling
-            if last_input_check.elapsed() >= input_rate {
-                if let Some(action) = self.get_user_input()? {
-                    match action {
-                        Action::Quit => self.should_quit = true,
-                        Action::DebugDrawCurrentPiece => {
-                            if let Some(ap) = self.game_board.active_peice {
-                                self.game_board.draw_to_board(ap.pos, ap.tetromino);
-                                let new_ap = ActivePiece {
-                                    pos: Vec2::new(3, 0),
-                                    tetromino: Tetromino::new(
-                                        TetrominoShape::pick_random_shape(),
-                                        TetrominoRotation::Zero,
-                                    ),
-                                };
-                                self.game_board.active_peice = Some(new_ap);
-                            }
-                        }
-                        Action::MovePiece(direction) => {
-                            match direction {
-                                MoveDirection::Left => {
-                                    if let Some(ap) = self.game_board.active_peice {
-                                        self.game_board.active_peice = Some(ActivePiece {
-                                            pos: Vec2::new(ap.pos.x - 1, ap.pos.y),
-                                            tetromino: ap.tetromino,
-                                        });
-                                    }
-                                }
-                                MoveDirection::Right => {
-                                    if let Some(ap) = self.game_board.active_peice {
-                                        self.game_board.active_peice = Some(ActivePiece {
-                                            pos: Vec2::new(ap.pos.x + 1, ap.pos.y),
-                                            tetromino: ap.tetromino,
-                                        });
-                                    }
-                                }
-                                MoveDirection::Down => {
-                                    if let Some(ap) = self.game_board.active_peice {
-                                        last_gravity_frame_update = std::time::Instant::now();
-                                        self.game_board.active_peice = Some(ActivePiece {
-                                            pos: Vec2::new(ap.pos.x, ap.pos.y + 1),
-                                            tetromino: ap.tetromino,
-                                        });
-                                    }
-                                }
-                                MoveDirection::CCW => {
-                                    if let Some(ap) = self.game_board.active_peice {
-                                        let new_tetromino = ap.tetromino.rotate(MoveDirection::CCW);
-                                        let new_ap = ActivePiece {
-                                            pos: ap.pos,
-                                            tetromino: new_tetromino,
-                                        };
-                                        self.game_board.active_peice = Some(new_ap);
-                                    }
-                                }
-                                MoveDirection::CW => {
-                                    if let Some(ap) = self.game_board.active_peice {
-                                        let new_tetromino = ap.tetromino.rotate(MoveDirection::CW);
-                                        let new_ap = ActivePiece {
-                                            pos: ap.pos,
-                                            tetromino: new_tetromino,
-                                        };
-                                        self.game_board.active_peice = Some(new_ap);
-                                    }
-                                }
-                                MoveDirection::FirmDrop => todo!(),
-                            }
-                            needs_redraw = true;
-                        }
-                    };
-                    last_input_check = std::time::Instant::now();
+    // Copies a setting the player changed into the settings that get saved
+    fn keep(&mut self, setting: Setting) {
+        let (options, saved) = (&self.options, &mut self.saved);
+        match setting {
+            Setting::Preset | Setting::Bind(_) => saved.keymap = options.keymap.clone(),
+            Setting::Das => saved.game.handling.das = options.game.handling.das,
+            Setting::Arr => saved.game.handling.arr = options.game.handling.arr,
+            Setting::Sdf => saved.game.handling.sdf = options.game.handling.sdf,
+            Setting::Preview => saved.game.preview_count = options.game.preview_count,
+            Setting::Ghost => saved.ghost = options.ghost,
+            Setting::SprintLines => saved.sprint_lines = options.sprint_lines,
+            Setting::UltraSeconds => saved.ultra_seconds = options.ultra_seconds,
+            Setting::StartLevel => saved.game.start_level = options.game.start_level,
+            Setting::MarathonLines => saved.marathon_lines = options.marathon_lines,
+        }
+    }
+
+    fn setting_value(&self, setting: Setting) -> String {
+        let game = &self.options.game;
+        let handling = &game.handling;
+        match setting {
+            Setting::Preset => self.options.keymap.preset().name().to_string(),
+            Setting::Das => format!(
+                "{} {} ms",
+                slider(handling.das, MAX_DAS),
+                config::frames_to_ms(handling.das)
+            ),
+            Setting::Arr => format!(
+                "{} {} ms",
+                slider(handling.arr, MAX_ARR),
+                config::frames_to_ms(handling.arr)
+            ),
+            Setting::Sdf => format!("{} {}x", slider(handling.sdf, MAX_SDF), handling.sdf),
+            Setting::Preview => format!(
+                "{} {}",
+                slider(game.preview_count as u32, MAX_PREVIEW as u32),
+                game.preview_count
+            ),
+            Setting::Ghost => String::from(if self.options.ghost { "on" } else { "off" }),
+            Setting::SprintLines => format!("< {} lines >", self.options.sprint_lines),
+            Setting::UltraSeconds => {
+                let seconds = self.options.ultra_seconds;
+                format!("< {}:{:02} >", seconds / 60, seconds % 60)
+            }
+            Setting::StartLevel => format!("< {} >", self.options.game.start_level),
+            Setting::MarathonLines => match self.options.marathon_lines {
+                0 => String::from("< endless >"),
+                lines => format!("< {} lines >", lines),
+            },
+            Setting::Bind(action) if self.capturing == Some(action) => {
+                String::from("press a key, esc to cancel")
+            }
+            Setting::Bind(action) => self.options.keymap.keys(action).join(" "),
+        }
+    }
+
+    // Writes the saved settings out, keeping any error to show the player
+    fn save_settings(&mut self) {
+        let saved = match &self.config_dir {
+            Some(dir) => config::save(dir, &self.saved),
+            None => Ok(()),
+        };
+        self.settings_error = saved.err();
+    }
+
+    // Saves the settings on the way back to the title from a screen that
+    // edits them. If they can't be saved the screen stays up with the error
+    fn leave_settings(&mut self) {
+        self.save_settings();
+        if self.settings_error.is_none() {
+            self.enter(Screen::Title);
+        }
+    }
+
+    // Drains every pending key event without blocking
+    fn get_user_input(&mut self) -> Result<Vec<(Key, crossterm::event::KeyEventKind)>> {
+        use crossterm::event as c_event;
+
+        let mut keys = Vec::new();
+        while c_event::poll(std::time::Duration::ZERO)? {
+            let c_event::Event::Key(key) = c_event::read()? else {
+                continue;
+            };
+            if key.kind == c_event::KeyEventKind::Release {
+                self.keyboard_enhanced = true;
+            }
+            // With the enhanced protocol shifted letters arrive lowercase, which
+            // `from_event` takes care of
+            if let Some(code) = Key::from_event(key.code, key.modifiers) {
+                keys.push((code, key.kind));
+            }
+        }
+        Ok(keys)
+    }
+
+    // Handles a key while the game is being played, queueing game inputs
+    fn play_key(&mut self, key: Key, kind: crossterm::event::KeyEventKind) {
+        use crossterm::event::KeyEventKind;
+
+        let Some(action) = self.options.keymap.action(key) else {
+            return;
+        };
+        match (action, kind) {
+            (Action::Play(command), KeyEventKind::Release) => {
+                self.inputs.push(Input::Release(command));
+            }
+            (_, KeyEventKind::Release) => {}
+            // Held keys are repeated by auto shift, not the terminal
+            (_, KeyEventKind::Repeat) if self.keyboard_enhanced => {}
+            (Action::Quit, _) => self.should_quit = true,
+            (Action::Pause, _) => self.enter(Screen::Paused),
+            (Action::ToggleGhost, _) => self.options.ghost = !self.options.ghost,
+            (Action::Play(command), _) => {
+                self.inputs.push(Input::Press(command));
+                if !self.keyboard_enhanced {
+                    self.inputs.push(Input::Release(command));
                 }
             }
+        }
+    }
 
-            // If a move action occured, recompute ghost block
+    // Runs one frame of the app. Keys go to the menu or the game depending on
+    // the screen, and the game only advances while it's being played
+    fn step(&mut self, keys: &[(Key, crossterm::event::KeyEventKind)]) {
+        use crossterm::event::KeyEventKind;
 
-            // Gravity handling
-            if last_gravity_frame_update.elapsed() >= gravity_frame_rate {
-                if let Some(ap) = self.game_board.active_peice {
-                    let new_ap = ActivePiece {
-                        pos: ap.pos + Vec2::new(0, 1),
-                        tetromino: ap.tetromino,
-                    };
-                    // check if new ap is valid location
-                    // else draw to board, istantiate new peice
-                    self.game_board.active_peice = Some(new_ap);
+        for &(key, kind) in keys {
+            if self.screen == Screen::Playing {
+                self.play_key(key, kind);
+            } else if kind == KeyEventKind::Release {
+                if let Some(Action::Play(command)) = self.options.keymap.action(key) {
+                    self.inputs.push(Input::Release(command));
+                }
+            } else if let Some(action) = self.capturing.take() {
+                // Escape is kept back for cancelling, so it can't be bound here
+                if key.code() != crossterm::event::KeyCode::Esc {
+                    self.options.keymap.toggle(key, action);
+                    self.keep(Setting::Bind(action));
                 }
+            } else if let Some(key) = MenuKey::from_code(key.code()) {
+                self.menu_key(key);
+            }
+        }
+        if self.screen != Screen::Playing {
+            return;
+        }
 
-                last_gravity_frame_update = std::time::Instant::now();
-                needs_redraw = true;
+        let inputs = std::mem::take(&mut self.inputs);
+        for event in self.game.step(&inputs) {
+            match event {
+                Event::PieceLocked { award, .. } if award.points > 0 => {
+                    self.debug_text = format!("{}\nlines: {}\n", award, self.game.lines());
+                }
+                Event::LevelUp(level) => {
+                    self.debug_text += &format!("level {}\n", level);
+                    if let Some(log) = &mut self.level_log {
+                        log.level_up(level, self.game.lines(), self.game.frame());
+                    }
+                }
+                Event::Finished => self.record_result(),
+                // An ultra score stands however the game ended
+                Event::TopOut(_) if self.mode == Mode::Ultra => self.record_result(),
+                _ => {}
+            }
+            if let Some(splits) = &mut self.splits {
+                splits.update(self.game.lines(), self.game.frame());
             }
+        }
+        let moved = inputs
+            .iter()
+            .any(|input| matches!(input, Input::Press(Command::Move(_))));
+        if let Some(kick) = self
+            .game
+            .board()
+            .active_peice
+            .and_then(|ap| ap.last_kick)
+            .filter(|_| moved)
+        {
+            self.debug_text = format!("kick: {}\n", kick);
+        }
+        if self.game.state() != GameState::Playing {
+            self.enter(Screen::GameOver);
+        }
+    }
 
-            // Redraw frame if needed
-            if needs_redraw {
+    // Puts a finished game in the records if it earned a place there. A
+    // sprint only counts if it reaches its goal, but an ultra that tops out
+    // early still keeps its score
+    fn record_result(&mut self) {
+        let note = match self.mode {
+            Mode::Endless | Mode::Marathon => return,
+            Mode::Sprint => {
+                let Some(splits) = &mut self.splits else {
+                    return;
+                };
+                // The finishing clear comes in the same step as the finish
+                splits.update(self.game.lines(), self.game.frame());
+                if !self.records.submit_sprint(splits.target, &splits.frames) {
+                    return;
+                }
+                String::from("New personal best!")
+            }
+            Mode::Ultra => {
+                let seconds = self.options.ultra_seconds;
+                match self.records.submit_ultra(seconds, self.game.score()) {
+                    None => return,
+                    Some(0) => String::from("New high score!"),
+                    Some(place) => format!("#{} on the leaderboard", place + 1),
+                }
+            }
+        };
+        let saved = match &self.data_dir {
+            Some(dir) => self.records.save(dir),
+            None => Ok(()),
+        };
+        self.result_note = Some(match saved {
+            Ok(()) => note,
+            Err(error) => format!("Couldn't save records: {}", error),
+        });
+    }
+
+    fn game_loop(&mut self, mut terminal: Terminal) -> Result<()> {
+        use crossterm::event as c_event;
+
+        let frame_time = std::time::Duration::from_secs(1) / gravity::FRAME_RATE;
+        // Don't try to catch up after the process was suspended or stalled
+        let max_behind = frame_time * 15;
+        let mut next_frame = std::time::Instant::now();
+        let mut inputs = Vec::new();
+
+        loop {
+            // Sleep until the next frame is due, waking early to pick up input
+            let now = std::time::Instant::now();
+            if next_frame > now {
+                c_event::poll(next_frame - now)?;
+            } else if now - next_frame > max_behind {
+                next_frame = now;
+            }
+            inputs.extend(self.get_user_input()?);
+
+            // Input that arrived since the last frame is applied to the next
+            // one, rendering happens once however many frames ran
+            let mut stepped = false;
+            while std::time::Instant::now() >= next_frame {
+                self.step(&inputs);
+                inputs.clear();
+                next_frame += frame_time;
+                stepped = true;
+            }
+            if stepped {
                 terminal.draw(|frame: &mut tui::Frame<'_>| self.make_frame(frame))?;
-                needs_redraw = false; // reset flag after redrawing
             }
 
-            // Check for quit condition
             if self.should_quit {
                 break;
             }
-
-            let time_now = std::time::Instant::now();
-            let loop_duration_already_served = time_now.duration_since(loop_start);
-            if let Some(sleep_time) = loop_rate.checked_sub(loop_duration_already_served) {
-                std::thread::sleep(sleep_time);
-            }
         }
         Ok(())
     }
@@ -323,10 +880,18 @@ impl App {
             return;
         }
 
+        if matches!(
+            self.screen,
+            Screen::Title | Screen::ModeSelect | Screen::Settings
+        ) {
+            self.render_menu(frame, frame_area);
+            return;
+        }
+
         let top_level_layout = tui::Layout::default()
             .direction(tui::Direction::Vertical)
             .constraints([
-                tui::Constraint::Length(self.game_board.grid_dims_chars.y as u16),
+                tui::Constraint::Length(BOARD_CHARS.y as u16),
                 tui::Constraint::Min(10),
             ])
             .split(frame_area);
@@ -334,18 +899,27 @@ impl App {
         let debug_layout = top_level_layout[1];
         let game_layout = top_level_layout[0];
 
-        let wing_width = (game_layout.width - self.game_board.grid_dims_chars.x as u16) / 2;
+        let wing_width = (game_layout.width - BOARD_CHARS.x as u16) / 2;
 
         let game_layout = tui::Layout::default()
             .direction(tui::Direction::Horizontal)
             .constraints([
                 tui::Constraint::Length(wing_width),
-                tui::Constraint::Length(self.game_board.grid_dims_chars.x as u16),
+                tui::Constraint::Length(BOARD_CHARS.x as u16),
                 tui::Constraint::Length(wing_width),
             ])
             .split(top_level_layout[0]);
 
-        let left = game_layout[0];
+        // Hold fits a piece two tiles tall, the rest of the wing is the mode's
+        let left_layout = tui::Layout::default()
+            .direction(tui::Direction::Vertical)
+            .constraints([
+                tui::Constraint::Length((2 * TILE_SIZE.y + 2) as u16),
+                tui::Constraint::Min(0),
+            ])
+            .split(game_layout[0]);
+        let left = left_layout[0];
+        let panel = left_layout[1];
         let board_layout = game_layout[1];
         let right = game_layout[2];
 
@@ -357,59 +931,725 @@ impl App {
             ),
             debug_layout,
         );
+        let hold_block = widgets::Block::default()
+            .title("hold")
+            .borders(widgets::Borders::ALL);
+        let next_block = widgets::Block::default()
+            .title("next")
+            .borders(widgets::Borders::ALL);
+        frame.render_widget(hold_block.clone(), left);
+        frame.render_widget(next_block.clone(), right);
         frame.render_widget(
-            widgets::Paragraph::new("this is the left block").block(
+            widgets::Paragraph::new(self.side_panel()).block(
                 widgets::Block::default()
-                    .title("left")
+                    .title(self.mode.name())
                     .borders(widgets::Borders::ALL),
             ),
-            left,
+            panel,
         );
+        // Nothing about the game is shown while paused, so pausing can't be
+        // used to plan ahead
+        if self.screen == Screen::Paused {
+            self.render_menu(frame, board_layout);
+            return;
+        }
+
+        if let Some(shape) = self.game.held() {
+            let tetromino = Tetromino::new(shape, TetrominoRotation::Zero);
+            // Greyed out until the hold is available again
+            let color = if self.game.hold_used() {
+                tui::Color::DarkGray
+            } else {
+                tui_color(tetromino.color)
+            };
+            render_tetromino(tetromino, color, hold_block.inner(left), frame.buffer_mut());
+        }
         frame.render_widget(
-            widgets::Paragraph::new("this is the right block").block(
-                widgets::Block::default()
-                    .title("right")
-                    .borders(widgets::Borders::ALL),
-            ),
-            right,
+            NextQueue {
+                shapes: self.game.next_queue(),
+            },
+            next_block.inner(right),
+        );
+        frame.render_widget(
+            BoardView {
+                board: self.game.board(),
+                show_ghost: self.options.ghost,
+            },
+            board_layout,
+        );
+
+        if self.screen == Screen::GameOver {
+            self.render_menu(frame, board_layout);
+        }
+    }
+
+    // Lines shown above the game over menu
+    fn results(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("Score: {}", self.game.score()),
+            format!("Lines: {}", self.game.lines()),
+            format!("Level: {}", self.game.level()),
+            format!("Time:  {}", format_time(self.game.frame())),
+            format!("Seed:  {}", self.game.seed()),
+        ];
+        lines.extend(self.result_note.clone());
+        if let Some(log) = &self.level_log {
+            lines.push(String::new());
+            lines.push(String::from("Level Lines Time"));
+            for split in log.summary(self.game.lines(), self.game.frame()) {
+                lines.push(format!(
+                    "{:>5} {:>5} {}",
+                    split.level,
+                    split.lines,
+                    format_time(split.frames)
+                ));
+            }
+        }
+        if self.mode == Mode::Ultra {
+            lines.push(String::new());
+            let scores = self.records.ultra_scores(self.options.ultra_seconds);
+            for (place, score) in scores.iter().enumerate().take(5) {
+                lines.push(format!("{:>2}. {}", place + 1, score));
+            }
+        }
+        lines
+    }
+
+    // What the panel under hold shows for the current mode
+    fn side_panel(&self) -> Vec<tui::Line<'static>> {
+        let frame = self.game.frame();
+        let score = tui::Line::from(format!("Score {}", self.game.score()));
+        let lines = tui::Line::from(format!("Lines {}", self.game.lines()));
+        match (self.mode, &self.splits) {
+            (Mode::Sprint, Some(splits)) => {
+                let lines_done = self.game.lines().min(splits.target);
+                let mut panel = vec![
+                    tui::Line::from(format_time(frame)),
+                    tui::Line::from(format!("{}/{} lines", lines_done, splits.target)),
+                    tui::Line::default(),
+                ];
+                panel.extend(splits.lines());
+                panel
+            }
+            (Mode::Ultra, _) => {
+                // Counts down, turning red for the last ten seconds
+                let left = self.ultra_frames().saturating_sub(frame);
+                let color = if left <= 10 * u64::from(gravity::FRAME_RATE) {
+                    tui::Color::Red
+                } else {
+                    tui::Color::Reset
+                };
+                let mut panel = vec![
+                    tui::Line::styled(format_time(left), tui::Style::default().fg(color)),
+                    score,
+                    lines,
+                ];
+                let best = self.records.ultra_scores(self.options.ultra_seconds);
+                if let Some(best) = best.first() {
+                    panel.push(tui::Line::default());
+                    panel.push(tui::Line::from(format!("Best {}", best)));
+                }
+                panel
+            }
+            (Mode::Marathon, _) if self.options.marathon_lines > 0 => vec![
+                tui::Line::from(format_time(frame)),
+                score,
+                tui::Line::from(format!(
+                    "Lines {}/{}",
+                    self.game.lines(),
+                    self.options.marathon_lines
+                )),
+                tui::Line::from(format!("Level {}", self.game.level())),
+            ],
+            _ => vec![
+                tui::Line::from(format_time(frame)),
+                score,
+                lines,
+                tui::Line::from(format!("Level {}", self.game.level())),
+            ],
+        }
+    }
+
+    // Lines shown above the settings menu
+    fn settings(&self) -> Vec<String> {
+        match self.save_error() {
+            Some(error) => vec![error],
+            None => vec![String::from("left/right change, enter binds a key")],
+        }
+    }
+
+    fn save_error(&self) -> Option<String> {
+        let error = self.settings_error.as_ref()?;
+        Some(format!("Couldn't save: {}", error))
+    }
+
+    // Draws the current screen's menu centred in `area`
+    fn render_menu(&self, frame: &mut tui::Frame, area: tui::Rect) {
+        let (title, header) = match self.screen {
+            Screen::Title => ("tetris", Vec::new()),
+            Screen::ModeSelect => ("mode", self.save_error().into_iter().collect()),
+            Screen::Playing => return,
+            Screen::Paused => ("paused", Vec::new()),
+            Screen::GameOver if self.game.state() == GameState::Finished => {
+                ("finished", self.results())
+            }
+            Screen::GameOver => ("game over", self.results()),
+            Screen::Settings => ("settings", self.settings()),
+        };
+        let labels: Vec<String> = self
+            .menu_choices()
+            .into_iter()
+            .map(|choice| match choice {
+                Choice::Setting(setting) => {
+                    format!("{:<13}{}", choice.label(), self.setting_value(setting))
+                }
+                choice => choice.label().to_string(),
+            })
+            .collect();
+        let labels: Vec<&str> = labels.iter().map(|label| label.as_str()).collect();
+        frame.render_widget(
+            Menu {
+                title,
+                header: &header,
+                items: &labels,
+                selected: self.selected,
+            },
+            area,
         );
-        frame.render_widget(self.game_board, board_layout);
     }
 }
 
 fn initialize_panic_handler() {
     let original_hook = std::panic::take_hook();
     std::panic::set_hook(Box::new(move |panic_info| {
+        crossterm::execute!(
+            std::io::stderr(),
+            crossterm::event::PopKeyboardEnhancementFlags
+        )
+        .unwrap();
         crossterm::execute!(std::io::stderr(), terminal::LeaveAlternateScreen).unwrap();
         terminal::disable_raw_mode().unwrap();
         original_hook(panic_info);
     }));
 }
 
+const USAGE: &str =
+    "usage: tetris [--randomizer 7bag|14bag|random|tgm|nes] [--seed N] [--preview 1-6] [--no-ghost] \
+     [--lock-reset infinite|extended|classic] [--das MS] [--arr MS] [--sdf N] \
+     [--gravity guideline|nes|20g] [--lines-per-level N]";
+
+// Applies command line flags over `options`
+fn parse_args(
+    mut options: Options,
+    mut args: impl Iterator<Item = String>,
+) -> std::result::Result<Options, String> {
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--randomizer" => {
+                let name = args.next().ok_or("--randomizer needs a value")?;
+                options.game.randomizer = RandomizerKind::from_name(&name)
+                    .ok_or(format!("unknown randomizer '{}'", name))?;
+            }
+            "--seed" => {
+                let seed = args.next().ok_or("--seed needs a value")?;
+                options.game.seed = Some(
+                    seed.parse()
+                        .map_err(|_| format!("seed '{}' is not a number", seed))?,
+                );
+            }
+            "--preview" => {
+                let count = args.next().ok_or("--preview needs a value")?;
+                options.game.preview_count = count
+                    .parse()
+                    .ok()
+                    .filter(|count| (1..=MAX_PREVIEW).contains(count))
+                    .ok_or(format!("preview must be 1 to {}", MAX_PREVIEW))?;
+            }
+            "--no-ghost" => options.ghost = false,
+            "--lock-reset" => {
+                let name = args.next().ok_or("--lock-reset needs a value")?;
+                options.game.lock_reset = LockResetPolicy::from_name(&name)
+                    .ok_or(format!("unknown lock reset policy '{}'", name))?;
+            }
+            "--das" | "--arr" => {
+                let value = args.next().ok_or(format!("{} needs a value", arg))?;
+                let ms: u32 = value
+                    .parse()
+                    .map_err(|_| format!("{} '{}' is not a number of milliseconds", arg, value))?;
+                // Handling runs on whole frames, round to the nearest one
+                if arg == "--das" {
+                    options.game.handling.das = config::handling_frames(&arg, ms, MAX_DAS)?;
+                } else {
+                    options.game.handling.arr = config::handling_frames(&arg, ms, MAX_ARR)?;
+                }
+            }
+            "--sdf" => {
+                let value = args.next().ok_or("--sdf needs a value")?;
+                options.game.handling.sdf = value
+                    .parse()
+                    .ok()
+                    .filter(|sdf| (1..=MAX_SDF).contains(sdf))
+                    .ok_or(format!(
+                        "sdf '{}' must be a whole number from 1 to {}",
+                        value, MAX_SDF
+                    ))?;
+            }
+            "--gravity" => {
+                let name = args.next().ok_or("--gravity needs a value")?;
+                options.game.gravity = GravityCurve::from_name(&name)
+                    .ok_or(format!("unknown gravity curve '{}'", name))?;
+            }
+            "--lines-per-level" => {
+                let value = args.next().ok_or("--lines-per-level needs a value")?;
+                options.game.lines_per_level = value
+                    .parse()
+                    .ok()
+                    .filter(|lines| *lines >= 1)
+                    .ok_or(format!("lines per level '{}' must be at least 1", value))?;
+            }
+            _ => return Err(format!("unexpected argument '{}'", arg)),
+        }
+    }
+    Ok(options)
+}
+
 fn main() -> Result<()> {
+    // Saved settings first so flags can override them for one run
+    let config_dir = config::dir();
+    let mut saved = Options::default();
+    if let Some(dir) = &config_dir {
+        if let Err(message) = config::load(dir, &mut saved) {
+            eprintln!("bad config in {}", message);
+            std::process::exit(2);
+        }
+    }
+    let options = match parse_args(saved.clone(), std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}\n{}", message, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let records_dir = records::dir();
+    let records = match &records_dir {
+        Some(dir) => Records::load(dir).unwrap_or_else(|message| {
+            eprintln!("bad records in {}", message);
+            std::process::exit(2);
+        }),
+        None => Records::default(),
+    };
+
+    let mut app = App::new(options);
+    app.saved = saved;
+    app.config_dir = config_dir;
+    app.records = records;
+    app.data_dir = records_dir;
+
     initialize_panic_handler();
 
     terminal::enable_raw_mode()?;
     crossterm::execute!(std::io::stderr(), terminal::EnterAlternateScreen)?;
+    // Ask for key release events so held keys can be timed. Terminals that
+    // don't speak the kitty keyboard protocol ignore this
+    crossterm::execute!(
+        std::io::stderr(),
+        crossterm::event::PushKeyboardEnhancementFlags(
+            crossterm::event::KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES
+                | crossterm::event::KeyboardEnhancementFlags::REPORT_EVENT_TYPES
+        )
+    )?;
 
     let mut terminal = tui::Terminal::new(tui::CrosstermBackend::new(std::io::stdout()))?;
     terminal.clear()?;
 
-    let mut app = App::new();
-
     // let mut t = TetrominoShape::get_data(TetrominoShape::L, TetrominoRotation::Zero);
     // t.move_down(5);
 
-    app.game_board.active_peice = Some(ActivePiece {
-        pos: Vec2::new(3, 0),
-        tetromino: Tetromino::new(TetrominoShape::pick_random_shape(), TetrominoRotation::Zero),
-    });
-
     let status = app.game_loop(terminal);
 
+    crossterm::execute!(
+        std::io::stderr(),
+        crossterm::event::PopKeyboardEnhancementFlags
+    )?;
     crossterm::execute!(std::io::stderr(), terminal::LeaveAlternateScreen)?;
     terminal::disable_raw_mode()?;
 
     status?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tetris_core::ActivePiece;
+    use tetris_core::SPAWN_POS;
+
+    fn key(spec: &str) -> Key {
+        Key::parse(spec).unwrap()
+    }
+
+    #[test]
+    fn test_ghost_rendered_at_landing_position() {
+        use widgets::Widget;
+
+        let mut board = GameBoard::new();
+        board.active_peice = Some(ActivePiece::spawn(TetrominoShape::O));
+        let area = tui::Rect::new(0, 0, 30, 44);
+        let ghost_corner = ((SPAWN_POS.x as usize * TILE_SIZE.x) as u16, 40);
+
+        let mut view = BoardView {
+            board: &board,
+            show_ghost: true,
+        };
+        let mut buf = tui::Buffer::empty(area);
+        view.render(area, &mut buf);
+        let symbol = &buf.get(ghost_corner.0, ghost_corner.1).symbol;
+        assert_eq!(symbol, &GHOST_CHARS[0][0].to_string());
+
+        view.show_ghost = false;
+        let mut buf = tui::Buffer::empty(area);
+        view.render(area, &mut buf);
+        assert_eq!(&buf.get(ghost_corner.0, ghost_corner.1).symbol, " ");
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let args = |a: &[&str]| parse_args(Options::default(), a.iter().map(|s| s.to_string()));
+        assert_eq!(args(&[]).unwrap().game.randomizer, RandomizerKind::SevenBag);
+        assert_eq!(
+            args(&["--randomizer", "tgm"]).unwrap().game.randomizer,
+            RandomizerKind::TgmHistory
+        );
+        assert!(args(&["--randomizer"]).is_err());
+        assert!(args(&["--randomizer", "nope"]).is_err());
+        assert!(args(&["--bogus"]).is_err());
+        assert_eq!(args(&["--seed", "42"]).unwrap().game.seed, Some(42));
+        assert!(args(&["--seed", "x"]).is_err());
+        assert_eq!(args(&["--preview", "3"]).unwrap().game.preview_count, 3);
+        assert!(args(&["--preview", "0"]).is_err());
+        assert!(args(&["--preview", "7"]).is_err());
+        assert!(args(&[]).unwrap().ghost);
+        assert!(!args(&["--no-ghost"]).unwrap().ghost);
+        assert_eq!(
+            args(&["--lock-reset", "classic"]).unwrap().game.lock_reset,
+            LockResetPolicy::Classic
+        );
+        assert!(args(&["--lock-reset", "never"]).is_err());
+        let handling = args(&["--das", "100", "--arr", "0", "--sdf", "40"])
+            .unwrap()
+            .game
+            .handling;
+        assert_eq!(handling.das, 6);
+        assert_eq!(handling.arr, 0);
+        assert_eq!(handling.sdf, 40);
+        assert!(args(&["--sdf", "0"]).is_err());
+        assert!(args(&["--sdf", "41"]).is_err());
+        assert!(args(&["--das", "fast"]).is_err());
+        assert!(args(&["--das", "100000000"]).is_err());
+        assert!(args(&["--arr", "200"]).is_err());
+        assert_eq!(
+            args(&["--gravity", "nes"]).unwrap().game.gravity,
+            GravityCurve::Nes
+        );
+        assert_eq!(
+            args(&["--lines-per-level", "5"])
+                .unwrap()
+                .game
+                .lines_per_level,
+            5
+        );
+        assert!(args(&["--lines-per-level", "0"]).is_err());
+    }
+
+    #[test]
+    fn test_restart_keeps_pinned_seed() {
+        let mut app = App::new(Options {
+            game: GameOptions {
+                seed: Some(99),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        app.start_game(Mode::Endless);
+        app.start_game(Mode::Endless);
+        assert_eq!(app.game.seed(), 99);
+    }
+
+    #[test]
+    fn test_presses_are_taps_until_releases_are_seen() {
+        use crossterm::event::KeyEventKind;
+
+        let left = (key("h"), KeyEventKind::Press);
+        let mut app = App::new(Options::default());
+        app.start_game(Mode::Endless);
+        let x = app.game.board().active_peice.unwrap().pos.x;
+        app.step(&[left]);
+        for _ in 0..30 {
+            app.step(&[]);
+        }
+        // Moved once, the terminal is left to repeat the key
+        assert_eq!(app.game.board().active_peice.unwrap().pos.x, x - 1);
+
+        let mut app = App::new(Options::default());
+        app.start_game(Mode::Endless);
+        app.keyboard_enhanced = true;
+        app.step(&[left]);
+        for _ in 0..30 {
+            app.step(&[]);
+        }
+        // Held, so auto shift carries it to the wall
+        assert_eq!(app.game.board().active_peice.unwrap().pos.x, 0);
+    }
+
+    #[test]
+    fn test_keys_go_through_the_keymap() {
+        use crossterm::event::KeyEventKind;
+
+        let mut app = App::new(Options {
+            keymap: Keymap::from_preset(keymap::Preset::Arrows),
+            ..Options::default()
+        });
+        app.start_game(Mode::Endless);
+        let x = app.game.board().active_peice.unwrap().pos.x;
+        app.step(&[(key("h"), KeyEventKind::Press)]);
+        assert_eq!(app.game.board().active_peice.unwrap().pos.x, x);
+        app.step(&[(key("left"), KeyEventKind::Press)]);
+        assert_eq!(app.game.board().active_peice.unwrap().pos.x, x - 1);
+    }
+
+    #[test]
+    fn test_menus_lead_from_title_into_a_game() {
+        use crossterm::event::KeyEventKind;
+
+        let press = |key| [(key, KeyEventKind::Press)];
+        let mut app = App::new(Options::default());
+        assert_eq!(app.screen, Screen::Title);
+        app.step(&press(key("down")));
+        app.step(&press(key("enter")));
+        assert_eq!(app.screen, Screen::Settings);
+        app.step(&press(key("esc")));
+        assert_eq!(app.screen, Screen::Title);
+        app.step(&press(key("enter")));
+        assert_eq!(app.screen, Screen::ModeSelect);
+        app.step(&press(key("enter")));
+        assert_eq!(app.screen, Screen::Playing);
+        let frame = app.game.frame();
+        app.step(&[]);
+        assert_eq!(app.game.frame(), frame + 1);
+    }
+
+    #[test]
+    fn test_settings_screen_edits_and_saves() {
+        use crossterm::event::KeyEventKind;
+
+        let press = |key| [(key, KeyEventKind::Press)];
+        let dir = std::env::temp_dir().join(format!("tetris-settings-{}", std::process::id()));
+        // Flags for this run only, over default settings
+        let flags = ["--no-ghost", "--preview", "1", "--sdf", "40"].map(String::from);
+        let mut app = App::new(parse_args(Options::default(), flags.into_iter()).unwrap());
+        app.saved = Options::default();
+        app.config_dir = Some(dir.clone());
+        app.choose(Choice::Settings);
+        let row = |app: &App, setting| {
+            let choices = app.menu_choices();
+            choices
+                .iter()
+                .position(|c| *c == Choice::Setting(setting))
+                .unwrap()
+        };
+
+        app.selected = row(&app, Setting::Das);
+        let das = app.options.game.handling.das;
+        app.step(&press(key("right")));
+        assert_eq!(app.options.game.handling.das, das + 1);
+
+        let hold = Action::Play(Command::Hold);
+        app.selected = row(&app, Setting::Bind(hold));
+        app.step(&press(key("enter")));
+        assert_eq!(app.capturing, Some(hold));
+        app.step(&press(key("v")));
+        assert_eq!(app.options.keymap.action(key("v")), Some(hold));
+        // Escape while capturing cancels rather than leaving
+        app.step(&press(key("enter")));
+        app.step(&press(key("esc")));
+        assert_eq!(app.capturing, None);
+        assert_eq!(app.screen, Screen::Settings);
+
+        app.step(&press(key("esc")));
+        assert_eq!(app.screen, Screen::Title);
+        let mut saved = Options::default();
+        config::load(&dir, &mut saved).unwrap();
+        assert_eq!(saved.game.handling.das, das + 1);
+        assert_eq!(saved.keymap, app.options.keymap);
+        assert!(saved.ghost);
+        assert_eq!(
+            saved.game.preview_count,
+            GameOptions::default().preview_count
+        );
+        assert_eq!(saved.game.handling.sdf, GameOptions::default().handling.sdf);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_sprint_runs_to_the_chosen_line_goal() {
+        use crossterm::event::KeyEventKind;
+
+        let press = |key| [(key, KeyEventKind::Press)];
+        let mut app = App::new(Options::default());
+        app.choose(Choice::Play);
+        app.selected = 2;
+        app.step(&press(key("right")));
+        app.step(&press(key("right")));
+        assert_eq!(app.options.sprint_lines, 100);
+        app.step(&press(key("left")));
+        app.selected = 1;
+        app.step(&press(key("enter")));
+        assert_eq!(app.screen, Screen::Playing);
+        assert_eq!(app.game.options().goal, Goal::Lines(40));
+        assert_eq!(app.splits.as_ref().unwrap().target, 40);
+
+        // Restarting keeps the mode, endless has no splits
+        app.start_game(Mode::Endless);
+        assert!(app.splits.is_none());
+        app.mode = Mode::Sprint;
+        app.choose(Choice::Restart);
+        assert!(app.splits.is_some());
+    }
+
+    #[test]
+    fn test_mode_select_saves_chosen_goals() {
+        let dir = std::env::temp_dir().join(format!("tetris-modes-{}", std::process::id()));
+        let mut app = App::new(Options::default());
+        app.config_dir = Some(dir.clone());
+        let load = || {
+            let mut saved = Options::default();
+            config::load(&dir, &mut saved).unwrap();
+            saved
+        };
+
+        app.choose(Choice::Play);
+        app.selected = 2;
+        app.menu_key(MenuKey::Left);
+        app.menu_key(MenuKey::Back);
+        assert_eq!(app.screen, Screen::Title);
+        assert_eq!(load().sprint_lines, 20);
+
+        app.choose(Choice::Play);
+        app.selected = 4;
+        app.menu_key(MenuKey::Right);
+        app.choose(Choice::Ultra);
+        assert_eq!(load().ultra_seconds, 180);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_ultra_ends_on_time_and_ranks_the_score() {
+        let mut app = App::new(Options {
+            ultra_seconds: 60,
+            ..Options::default()
+        });
+        app.choose(Choice::Play);
+        app.selected = 4;
+        app.menu_key(MenuKey::Left);
+        assert_eq!(app.options.ultra_seconds, 60);
+        app.choose(Choice::Ultra);
+        assert_eq!(app.game.options().goal, Goal::Frames(60 * 60));
+        while app.screen == Screen::Playing {
+            app.step(&[]);
+        }
+        assert_eq!(app.game.state(), GameState::Finished);
+        assert_eq!(app.game.frame(), 60 * 60);
+        assert_eq!(app.records.ultra_scores(60), [0]);
+        assert_eq!(app.result_note.as_deref(), Some("New high score!"));
+
+        // Topping out before time still puts the score on the board
+        app.start_game(Mode::Ultra);
+        while app.screen == Screen::Playing {
+            app.step(&[(key("space"), crossterm::event::KeyEventKind::Press)]);
+        }
+        assert_eq!(app.game.state(), GameState::GameOver);
+        assert!(app.game.frame() < 60 * 60);
+        assert_eq!(app.records.ultra_scores(60).len(), 2);
+        assert!(app.result_note.is_some());
+    }
+
+    #[test]
+    fn test_marathon_starts_on_the_chosen_level() {
+        let mut app = App::new(Options {
+            marathon_lines: 0,
+            game: GameOptions {
+                start_level: 4,
+                lines_per_level: 3,
+                ..GameOptions::default()
+            },
+            ..Options::default()
+        });
+        app.choose(Choice::Marathon);
+        let options = app.game.options();
+        assert_eq!(options.goal, Goal::Endless);
+        assert_eq!(options.start_level, 4);
+        assert_eq!(options.lines_per_level, marathon::LINES_PER_LEVEL);
+        assert_eq!(app.game.level(), 4);
+
+        for _ in 0..90 {
+            app.step(&[]);
+        }
+        let results = app.results().join("\n");
+        assert!(results.contains("    4     0 0:01.500"), "{}", results);
+
+        app.options.marathon_lines = 150;
+        app.choose(Choice::Restart);
+        assert_eq!(app.game.options().goal, Goal::Lines(150));
+
+        app.start_game(Mode::Endless);
+        assert_eq!(app.game.level(), 1);
+    }
+
+    #[test]
+    fn test_pause_freezes_game_and_keeps_releases() {
+        use crossterm::event::KeyEventKind;
+
+        let mut app = App::new(Options::default());
+        app.start_game(Mode::Endless);
+        app.keyboard_enhanced = true;
+        app.step(&[(key("h"), KeyEventKind::Press)]);
+        app.step(&[(key("esc"), KeyEventKind::Press)]);
+        assert_eq!(app.screen, Screen::Paused);
+        let frame = app.game.frame();
+        let x = app.game.board().active_peice.unwrap().pos.x;
+        app.step(&[(key("h"), KeyEventKind::Release)]);
+        for _ in 0..30 {
+            app.step(&[]);
+        }
+        assert_eq!(app.game.frame(), frame);
+
+        // Resuming delivers the release, so the piece doesn't keep sliding
+        app.step(&[(key("esc"), KeyEventKind::Press)]);
+        assert_eq!(app.screen, Screen::Playing);
+        for _ in 0..30 {
+            app.step(&[]);
+        }
+        assert_eq!(app.game.board().active_peice.unwrap().pos.x, x);
+    }
+
+    #[test]
+    fn test_pause_hides_the_board() {
+        // Pieces are the only coloured cells on screen
+        let coloured_cells = |app: &App| {
+            let mut terminal = tui::Terminal::new(tui::backend::TestBackend::new(120, 60)).unwrap();
+            let frame = terminal.draw(|frame| app.make_frame(frame)).unwrap();
+            frame
+                .buffer
+                .content
+                .iter()
+                .filter(|cell| cell.fg != tui::Color::Reset)
+                .count()
+        };
+        let mut app = App::new(Options::default());
+        app.start_game(Mode::Endless);
+        assert!(coloured_cells(&app) > 0);
+        app.enter(Screen::Paused);
+        assert_eq!(coloured_cells(&app), 0);
+    }
+}
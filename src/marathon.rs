@@ -0,0 +1,74 @@
+/// The line goals a marathon can have. Zero plays on until topping out
+pub const MARATHON_LINES: [usize; 3] = [150, 200, 0];
+/// The highest level a marathon can be started on
+pub const MAX_START_LEVEL: usize = 15;
+/// Marathon levels up on a fixed number of lines, whatever the settings say
+pub const LINES_PER_LEVEL: usize = 10;
+
+/// One level's share of a marathon
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LevelSplit {
+    pub level: usize,
+    pub lines: usize,
+    pub frames: u64,
+}
+
+/// When each level of a marathon began, for the summary at the end
+pub struct LevelLog {
+    // Level, total lines and frame at the start of each level
+    starts: Vec<(usize, usize, u64)>,
+}
+
+impl LevelLog {
+    pub fn new(start_level: usize) -> LevelLog {
+        LevelLog {
+            starts: vec![(start_level, 0, 0)],
+        }
+    }
+
+    pub fn level_up(&mut self, level: usize, lines: usize, frame: u64) {
+        self.starts.push((level, lines, frame));
+    }
+
+    /// Lines cleared and time spent on every level so far, where the game is
+    /// now at `lines` and `frame`
+    pub fn summary(&self, lines: usize, frame: u64) -> Vec<LevelSplit> {
+        let ends = self
+            .starts
+            .iter()
+            .skip(1)
+            .map(|(_, lines, frame)| (*lines, *frame))
+            .chain([(lines, frame)]);
+        self.starts
+            .iter()
+            .zip(ends)
+            .map(|(start, end)| LevelSplit {
+                level: start.0,
+                lines: end.0 - start.1,
+                frames: end.1 - start.2,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_splits_lines_and_time_by_level() {
+        let mut log = LevelLog::new(3);
+        // A tetris at 8 lines puts the level up with 12 lines on level 3
+        log.level_up(4, 12, 600);
+        log.level_up(5, 20, 900);
+        let split = |level, lines, frames| LevelSplit {
+            level,
+            lines,
+            frames,
+        };
+        assert_eq!(
+            log.summary(23, 1000),
+            [split(3, 12, 600), split(4, 8, 300), split(5, 3, 100)]
+        );
+    }
+}
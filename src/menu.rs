@@ -0,0 +1,144 @@
+use ratatui::prelude as tui;
+use ratatui::widgets;
+
+/// What a key does while a menu is showing
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MenuKey {
+    Up,
+    Down,
+    /// Adjusts the selected item, for menus with values in them
+    Left,
+    Right,
+    Select,
+    Back,
+}
+impl MenuKey {
+    pub fn from_code(code: crossterm::event::KeyCode) -> Option<MenuKey> {
+        use crossterm::event::KeyCode;
+
+        match code {
+            KeyCode::Up | KeyCode::Char('k') | KeyCode::Char('w') => Some(MenuKey::Up),
+            KeyCode::Down | KeyCode::Char('j') | KeyCode::Char('s') => Some(MenuKey::Down),
+            KeyCode::Left | KeyCode::Char('h') | KeyCode::Char('a') => Some(MenuKey::Left),
+            KeyCode::Right | KeyCode::Char('l') | KeyCode::Char('d') => Some(MenuKey::Right),
+            // Not space, a hard drop spammed into game over shouldn't pick anything
+            KeyCode::Enter => Some(MenuKey::Select),
+            KeyCode::Esc | KeyCode::Backspace => Some(MenuKey::Back),
+            _ => None,
+        }
+    }
+}
+
+/// Moves a selection through `len` items, wrapping at either end
+pub fn navigate(selected: usize, len: usize, key: MenuKey) -> usize {
+    match key {
+        _ if len == 0 => 0,
+        MenuKey::Up => (selected + len - 1) % len,
+        MenuKey::Down => (selected + 1) % len,
+        MenuKey::Left | MenuKey::Right | MenuKey::Select | MenuKey::Back => selected,
+    }
+}
+
+/// A boxed list of choices with one highlighted, centred in the area it is
+/// given. Lines in `header` are shown above the choices and can't be picked
+pub struct Menu<'a> {
+    pub title: &'a str,
+    pub header: &'a [String],
+    pub items: &'a [&'a str],
+    pub selected: usize,
+}
+
+impl Menu<'_> {
+    fn size(&self) -> (u16, u16) {
+        let widest = self
+            .header
+            .iter()
+            .map(|line| line.chars().count())
+            .chain(self.items.iter().map(|item| item.chars().count() + 2))
+            .chain(std::iter::once(self.title.chars().count()))
+            .max()
+            .unwrap_or(0);
+        let gap = usize::from(!self.header.is_empty());
+        let height = self.header.len() + gap + self.items.len();
+        // Borders plus a column of padding either side
+        (widest as u16 + 4, height as u16 + 2)
+    }
+}
+
+impl widgets::Widget for Menu<'_> {
+    fn render(self, area: tui::Rect, buf: &mut tui::Buffer) {
+        let (width, height) = self.size();
+        let width = width.min(area.width);
+        let height = height.min(area.height);
+        let area = tui::Rect::new(
+            area.x + (area.width - width) / 2,
+            area.y + (area.height - height) / 2,
+            width,
+            height,
+        );
+
+        let mut lines: Vec<tui::Line> = self
+            .header
+            .iter()
+            .map(|line| tui::Line::from(line.as_str()))
+            .collect();
+        if !self.header.is_empty() {
+            lines.push(tui::Line::default());
+        }
+        for (i, item) in self.items.iter().enumerate() {
+            lines.push(if i == self.selected {
+                tui::Line::styled(
+                    format!("> {}", item),
+                    tui::Style::default().add_modifier(tui::Modifier::REVERSED),
+                )
+            } else {
+                tui::Line::from(format!("  {}", item))
+            });
+        }
+
+        widgets::Clear.render(area, buf);
+        widgets::Paragraph::new(lines)
+            .block(
+                widgets::Block::default()
+                    .title(self.title)
+                    .borders(widgets::Borders::ALL)
+                    .padding(widgets::Padding::horizontal(1)),
+            )
+            .render(area, buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use widgets::Widget;
+
+    #[test]
+    fn test_navigate_wraps() {
+        assert_eq!(navigate(0, 3, MenuKey::Up), 2);
+        assert_eq!(navigate(2, 3, MenuKey::Down), 0);
+        assert_eq!(navigate(1, 3, MenuKey::Select), 1);
+        assert_eq!(navigate(1, 3, MenuKey::Right), 1);
+        assert_eq!(navigate(0, 0, MenuKey::Down), 0);
+    }
+
+    #[test]
+    fn test_selected_item_is_marked() {
+        let area = tui::Rect::new(0, 0, 20, 6);
+        let mut buf = tui::Buffer::empty(area);
+        Menu {
+            title: "menu",
+            header: &[],
+            items: &["Play", "Quit"],
+            selected: 1,
+        }
+        .render(area, &mut buf);
+        let row = |y| {
+            (0..area.width)
+                .map(|x| buf.get(x, y).symbol.clone())
+                .collect::<String>()
+        };
+        assert!(row(2).contains("  Play"));
+        assert!(row(3).contains("> Quit"));
+    }
+}
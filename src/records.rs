@@ -0,0 +1,118 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+
+pub const RECORDS_FILE: &str = "records.toml";
+/// How many scores each leaderboard keeps
+pub const LEADERBOARD_SIZE: usize = 10;
+
+/// Where records live, `$XDG_DATA_HOME/tetris` on Linux. Records aren't
+/// settings, so they're kept apart from the config
+pub fn dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("tetris"))
+}
+
+/// Best results kept between runs
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Records {
+    // Best sprint splits in frames, keyed by the line target. TOML keys have
+    // to be strings
+    sprint: BTreeMap<String, Vec<u64>>,
+    // Best ultra scores, highest first, keyed by the time limit in seconds
+    ultra: BTreeMap<String, Vec<usize>>,
+}
+
+impl Records {
+    pub fn load(dir: &Path) -> Result<Records, String> {
+        let path = dir.join(RECORDS_FILE);
+        match config::read(&path)? {
+            Some(text) => toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e)),
+            None => Ok(Records::default()),
+        }
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<(), String> {
+        let text = toml::to_string(self).map_err(|e| e.to_string())?;
+        config::write_atomic(&dir.join(RECORDS_FILE), &text)
+    }
+
+    /// The splits of the fastest sprint to `lines`
+    pub fn sprint_best(&self, lines: usize) -> Option<&[u64]> {
+        self.sprint.get(&lines.to_string()).map(Vec::as_slice)
+    }
+
+    /// Keeps `splits` if they finished faster than the best sprint to `lines`.
+    /// True if they did
+    pub fn submit_sprint(&mut self, lines: usize, splits: &[u64]) -> bool {
+        let finish = |splits: &[u64]| splits.last().copied().unwrap_or(u64::MAX);
+        let faster = self
+            .sprint_best(lines)
+            .is_none_or(|best| finish(splits) < finish(best));
+        if faster {
+            self.sprint.insert(lines.to_string(), splits.to_vec());
+        }
+        faster
+    }
+
+    /// The ultra leaderboard for a `seconds` long game, highest first
+    pub fn ultra_scores(&self, seconds: u64) -> &[usize] {
+        self.ultra
+            .get(&seconds.to_string())
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Puts `score` on the leaderboard for `seconds` long ultra games if it's
+    /// good enough. Returns its place, 0 for the top
+    pub fn submit_ultra(&mut self, seconds: u64, score: usize) -> Option<usize> {
+        let scores = self.ultra.entry(seconds.to_string()).or_default();
+        // Below equal scores, the earlier one got there first
+        let place = scores.partition_point(|best| *best >= score);
+        if place >= LEADERBOARD_SIZE {
+            return None;
+        }
+        scores.insert(place, score);
+        scores.truncate(LEADERBOARD_SIZE);
+        Some(place)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_faster_sprints_are_kept() {
+        let mut records = Records::default();
+        assert!(records.submit_sprint(40, &[600, 1200, 1800, 2400]));
+        assert!(!records.submit_sprint(40, &[500, 1000, 1500, 2500]));
+        assert!(records.submit_sprint(40, &[700, 1300, 1900, 2300]));
+        assert_eq!(records.sprint_best(40), Some(&[700, 1300, 1900, 2300][..]));
+        assert_eq!(records.sprint_best(20), None);
+
+        let text = toml::to_string(&records).unwrap();
+        assert_eq!(toml::from_str::<Records>(&text).unwrap(), records);
+    }
+
+    #[test]
+    fn test_ultra_leaderboard_keeps_the_top_scores() {
+        let mut records = Records::default();
+        for score in 1..=LEADERBOARD_SIZE {
+            records.submit_ultra(120, score * 100);
+        }
+        assert_eq!(records.submit_ultra(120, 50), None);
+        assert_eq!(records.submit_ultra(120, 550), Some(5));
+        assert_eq!(records.submit_ultra(120, 5000), Some(0));
+        let scores = records.ultra_scores(120);
+        assert_eq!(scores.len(), LEADERBOARD_SIZE);
+        assert_eq!(scores[..2], [5000, 1000]);
+        assert!(!scores.contains(&100));
+        assert!(records.ultra_scores(60).is_empty());
+
+        let text = toml::to_string(&records).unwrap();
+        assert_eq!(toml::from_str::<Records>(&text).unwrap(), records);
+    }
+}
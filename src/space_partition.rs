@@ -98,7 +98,11 @@ pub fn calculate_layout(area: &Area, available_space: Rect) -> HashMap<String, R
             None => (available_space.width, available_space.height),
         };
 
-        let area_rect = Rect::new(current_offset, available_space.y, width, height);
+        let (x, y) = match area.partition {
+            Some(Partition::Vertical) => (available_space.x, current_offset),
+            _ => (current_offset, available_space.y),
+        };
+        let area_rect = Rect::new(x, y, width, height);
         layout.insert(sub_area.name.clone(), area_rect);
 
         current_offset += match area.partition {
@@ -0,0 +1,93 @@
+use ratatui::prelude as tui;
+
+use tetris_core::Game;
+
+use crate::format_time;
+
+/// The line targets a sprint can be run to
+pub const SPRINT_LINES: [usize; 3] = [20, 40, 100];
+/// Lines between split times
+pub const SPLIT_LINES: usize = 10;
+
+/// Split times for a sprint, in frames, against the best run to the same
+/// target
+pub struct Splits {
+    pub target: usize,
+    pub frames: Vec<u64>,
+    pub best: Option<Vec<u64>>,
+}
+
+impl Splits {
+    pub fn new(target: usize, best: Option<&[u64]>) -> Splits {
+        Splits {
+            target,
+            frames: Vec::new(),
+            best: best.map(<[u64]>::to_vec),
+        }
+    }
+
+    /// Takes a split for every multiple of `SPLIT_LINES` that `lines` has
+    /// passed. A clear that crosses two at once gives both the same time
+    pub fn update(&mut self, lines: usize, frame: u64) {
+        let reached = lines.min(self.target) / SPLIT_LINES;
+        while self.frames.len() < reached {
+            self.frames.push(frame);
+        }
+    }
+
+    /// How far behind the best run split `index` was, in frames. Negative
+    /// when ahead
+    pub fn delta(&self, index: usize) -> Option<i64> {
+        let best = self.best.as_ref()?.get(index)?;
+        let frame = self.frames.get(index)?;
+        Some(*frame as i64 - *best as i64)
+    }
+
+    /// The side panel lines for the splits taken so far, coloured green when
+    /// ahead of the best run and red when behind
+    pub fn lines(&self) -> Vec<tui::Line<'static>> {
+        let mut lines = Vec::new();
+        for (i, frame) in self.frames.iter().enumerate() {
+            let split = format!("{:>3} {}", (i + 1) * SPLIT_LINES, format_time(*frame));
+            lines.push(tui::Line::from(split));
+            if let Some(delta) = self.delta(i) {
+                let color = match delta {
+                    ..=-1 => tui::Color::Green,
+                    0 => tui::Color::Reset,
+                    1.. => tui::Color::Red,
+                };
+                let time = Game::frame_time(delta.unsigned_abs());
+                let delta = format!(
+                    "{}{}.{:03}",
+                    if delta < 0 { "-" } else { "+" },
+                    time.as_secs(),
+                    time.subsec_millis()
+                );
+                lines.push(tui::Line::styled(
+                    format!("{:>12}", delta),
+                    tui::Style::default().fg(color),
+                ));
+            }
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_splits_every_ten_lines_up_to_target() {
+        let mut splits = Splits::new(20, Some(&[500, 1000]));
+        splits.update(9, 100);
+        assert!(splits.frames.is_empty());
+        splits.update(10, 400);
+        assert_eq!(splits.delta(0), Some(-100));
+        // A tetris from 18 lines overshoots the target but takes one split
+        splits.update(22, 1100);
+        assert_eq!(splits.frames, [400, 1100]);
+        assert_eq!(splits.delta(1), Some(100));
+        assert_eq!(Splits::new(20, None).delta(0), None);
+    }
+}
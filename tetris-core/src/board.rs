@@ -0,0 +1,406 @@
+use itertools::Itertools;
+
+use crate::scoring::Spin;
+use crate::tetromino::Colors;
+use crate::tetromino::MoveDirection;
+use crate::tetromino::Tetromino;
+use crate::tetromino::TetrominoRotation;
+use crate::tetromino::TetrominoShape;
+use crate::utils::Vec2;
+
+pub const GAME_SIZE: Vec2<usize> = Vec2 { x: 10, y: 22 };
+// Rows at the top of the grid that pieces spawn into. These sit above the
+// 10x20 playfield proper, a piece locking entirely inside them is a lock out
+pub const BUFFER_ROWS: usize = 2;
+
+pub const SPAWN_POS: Vec2<i32> = Vec2 { x: 3, y: 0 };
+
+#[derive(Clone, Copy)]
+pub struct ActivePiece {
+    // Signed so that pieces whose blocks don't start in the first column of
+    // their bounding box (e.g. a vertical I) can sit flush against the left wall
+    pub pos: Vec2<i32>,
+    pub tetromino: Tetromino,
+    // Index into the SRS kick table used by the last successful move, if that
+    // move was a rotation. Needed for T-spin detection
+    pub last_kick: Option<usize>,
+}
+impl ActivePiece {
+    pub fn spawn(shape: TetrominoShape) -> Self {
+        Self {
+            pos: SPAWN_POS,
+            tetromino: Tetromino::new(shape, TetrominoRotation::Zero),
+            last_kick: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineClear {
+    Single,
+    Double,
+    Triple,
+    Tetris,
+}
+impl LineClear {
+    fn from_count(count: usize) -> Option<LineClear> {
+        match count {
+            1 => Some(LineClear::Single),
+            2 => Some(LineClear::Double),
+            3 => Some(LineClear::Triple),
+            4 => Some(LineClear::Tetris),
+            _ => None,
+        }
+    }
+    pub fn count(self) -> usize {
+        match self {
+            LineClear::Single => 1,
+            LineClear::Double => 2,
+            LineClear::Triple => 3,
+            LineClear::Tetris => 4,
+        }
+    }
+}
+
+/// The locked cells plus the piece currently in play
+#[derive(Clone, Copy)]
+pub struct GameBoard {
+    pub grid: [[Option<Colors>; GAME_SIZE.x]; GAME_SIZE.y],
+    pub active_peice: Option<ActivePiece>,
+}
+impl Default for GameBoard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl GameBoard {
+    pub fn new() -> Self {
+        Self {
+            grid: [[None; GAME_SIZE.x]; GAME_SIZE.y],
+            active_peice: None,
+        }
+    }
+    /// Returns true if `tetromino` placed at `pos` lies entirely inside the
+    /// grid and does not overlap any locked cells
+    pub fn fits(&self, pos: Vec2<i32>, tetromino: Tetromino) -> bool {
+        tetromino.blocks.iter().all(|(block_y, block_x)| {
+            let (grid_y, grid_x) = (pos.y + *block_y as i32, pos.x + *block_x as i32);
+            (0..GAME_SIZE.y as i32).contains(&grid_y)
+                && (0..GAME_SIZE.x as i32).contains(&grid_x)
+                && self.grid[grid_y as usize][grid_x as usize].is_none()
+        })
+    }
+    /// Number of rows `tetromino` can fall from `pos` before it lands
+    pub fn drop_distance(&self, pos: Vec2<i32>, tetromino: Tetromino) -> i32 {
+        let mut distance = 0;
+        while self.fits(pos + Vec2::new(0, distance + 1), tetromino) {
+            distance += 1;
+        }
+        distance
+    }
+    /// Attempts to move or rotate the active piece. Rotations try each SRS wall
+    /// kick in turn. The move is rejected, and false returned, if the resulting
+    /// position does not fit on the board. A sonic drop with nowhere to fall
+    /// isn't a move, so it leaves the piece and its last kick alone
+    pub fn try_move_active_piece(&mut self, direction: MoveDirection) -> bool {
+        let Some(ap) = self.active_peice else {
+            return false;
+        };
+        let offset = match direction {
+            MoveDirection::Left => Vec2::new(-1, 0),
+            MoveDirection::Right => Vec2::new(1, 0),
+            MoveDirection::Down => Vec2::new(0, 1),
+            MoveDirection::SonicDrop => match self.drop_distance(ap.pos, ap.tetromino) {
+                0 => return false,
+                distance => Vec2::new(0, distance),
+            },
+            MoveDirection::CCW | MoveDirection::CW => {
+                let tetromino = ap.tetromino.rotate(direction);
+                let kick = ap
+                    .tetromino
+                    .kicks(tetromino.rotation)
+                    .map(|(x, y)| ap.pos + Vec2::new(x, y))
+                    .find_position(|pos| self.fits(*pos, tetromino));
+                let Some((kick, pos)) = kick else {
+                    return false;
+                };
+                self.active_peice = Some(ActivePiece {
+                    pos,
+                    tetromino,
+                    last_kick: Some(kick),
+                });
+                return true;
+            }
+        };
+        let pos = ap.pos + offset;
+        if !self.fits(pos, ap.tetromino) {
+            return false;
+        }
+        self.active_peice = Some(ActivePiece {
+            pos,
+            tetromino: ap.tetromino,
+            last_kick: None,
+        });
+        true
+    }
+    pub fn is_blocked(&self, grid_y: i32, grid_x: i32) -> bool {
+        !(0..GAME_SIZE.y as i32).contains(&grid_y)
+            || !(0..GAME_SIZE.x as i32).contains(&grid_x)
+            || self.grid[grid_y as usize][grid_x as usize].is_some()
+    }
+    /// Classifies a lock of `ap` as a T-spin using the 3-corner rule. The two
+    /// corners the T points at decide full vs mini, unless the piece got there
+    /// with the last (TST) kick, which always counts as full
+    pub fn t_spin(&self, ap: &ActivePiece) -> Spin {
+        if ap.tetromino.shape != TetrominoShape::T {
+            return Spin::None;
+        }
+        let Some(kick) = ap.last_kick else {
+            return Spin::None;
+        };
+        let corner = |(y, x): (i32, i32)| self.is_blocked(ap.pos.y + y, ap.pos.x + x);
+        let (front, back) = match ap.tetromino.rotation {
+            TetrominoRotation::Zero => ([(0, 0), (0, 2)], [(2, 0), (2, 2)]),
+            TetrominoRotation::Ninety => ([(0, 2), (2, 2)], [(0, 0), (2, 0)]),
+            TetrominoRotation::OneEighty => ([(2, 0), (2, 2)], [(0, 0), (0, 2)]),
+            TetrominoRotation::TwoSeventy => ([(0, 0), (2, 0)], [(0, 2), (2, 2)]),
+        };
+        let front = front.into_iter().filter(|c| corner(*c)).count();
+        let back = back.into_iter().filter(|c| corner(*c)).count();
+        match (front, back) {
+            _ if front + back < 3 => Spin::None,
+            (2, _) => Spin::Full,
+            _ if kick == 4 => Spin::Full,
+            _ => Spin::Mini,
+        }
+    }
+    /// Returns true if every block of `tetromino` at `pos` is inside the buffer
+    /// rows above the playfield
+    pub fn is_above_playfield(pos: Vec2<i32>, tetromino: Tetromino) -> bool {
+        tetromino
+            .blocks
+            .iter()
+            .all(|(block_y, _)| pos.y + (*block_y as i32) < BUFFER_ROWS as i32)
+    }
+    /// Removes every completed row, shifting the rows above it down, and
+    /// reports how many rows were cleared
+    pub fn clear_lines(&mut self) -> Option<LineClear> {
+        let mut write_y = GAME_SIZE.y;
+        for read_y in (0..GAME_SIZE.y).rev() {
+            if self.grid[read_y].iter().all(|cell| cell.is_some()) {
+                continue;
+            }
+            write_y -= 1;
+            self.grid[write_y] = self.grid[read_y];
+        }
+        let cleared = write_y;
+        for row in self.grid.iter_mut().take(cleared) {
+            *row = [None; GAME_SIZE.x];
+        }
+        LineClear::from_count(cleared)
+    }
+    pub fn draw_to_board(&mut self, pos: Vec2<i32>, tetromino: Tetromino) {
+        for (_grid_y, _grid_x) in tetromino.blocks.iter() {
+            let (grid_y, grid_x) = (*_grid_y as i32 + pos.y, *_grid_x as i32 + pos.x);
+            self.grid[grid_y as usize][grid_x as usize] = Some(tetromino.color);
+        }
+    }
+}
+
+// Fills the bottom rows from a picture, '#' is a locked cell
+#[cfg(test)]
+pub(crate) fn board_from(rows: &[&str]) -> GameBoard {
+    let mut board = GameBoard::new();
+    let top = GAME_SIZE.y - rows.len();
+    for (y, row) in rows.iter().enumerate() {
+        for (x, c) in row.chars().enumerate() {
+            if c == '#' {
+                board.grid[top + y][x] = Some(Colors::Red);
+            }
+        }
+    }
+    board
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fits_inside_empty_board() {
+        let board = GameBoard::new();
+        let t = Tetromino::new(TetrominoShape::O, TetrominoRotation::Zero);
+        assert!(board.fits(Vec2::new(0, 0), t));
+        assert!(board.fits(Vec2::new(8, 20), t));
+    }
+
+    #[test]
+    fn test_fits_rejects_out_of_bounds() {
+        let board = GameBoard::new();
+        let t = Tetromino::new(TetrominoShape::O, TetrominoRotation::Zero);
+        assert!(!board.fits(Vec2::new(-1, 0), t));
+        assert!(!board.fits(Vec2::new(9, 0), t));
+        assert!(!board.fits(Vec2::new(0, 21), t));
+        assert!(!board.fits(Vec2::new(0, -1), t));
+    }
+
+    #[test]
+    fn test_fits_rejects_locked_cells() {
+        let mut board = GameBoard::new();
+        board.grid[5][5] = Some(Colors::Red);
+        let t = Tetromino::new(TetrominoShape::O, TetrominoRotation::Zero);
+        assert!(!board.fits(Vec2::new(4, 4), t));
+        assert!(board.fits(Vec2::new(6, 4), t));
+    }
+
+    #[test]
+    fn test_move_is_rejected_at_wall() {
+        let mut board = GameBoard::new();
+        board.active_peice = Some(ActivePiece {
+            pos: Vec2::new(0, 0),
+            tetromino: Tetromino::new(TetrominoShape::O, TetrominoRotation::Zero),
+            last_kick: None,
+        });
+        assert!(!board.try_move_active_piece(MoveDirection::Left));
+        assert_eq!(board.active_peice.unwrap().pos, Vec2::new(0, 0));
+        assert!(board.try_move_active_piece(MoveDirection::Right));
+        assert_eq!(board.active_peice.unwrap().pos, Vec2::new(1, 0));
+    }
+
+    #[test]
+    fn test_move_down_stops_at_floor() {
+        let mut board = GameBoard::new();
+        board.active_peice = Some(ActivePiece::spawn(TetrominoShape::O));
+        while board.try_move_active_piece(MoveDirection::Down) {}
+        assert_eq!(board.active_peice.unwrap().pos.y, GAME_SIZE.y as i32 - 2);
+    }
+
+    #[test]
+    fn test_clear_lines_collapses_rows() {
+        let mut board = GameBoard::new();
+        let bottom = GAME_SIZE.y - 1;
+        board.grid[bottom] = [Some(Colors::Red); GAME_SIZE.x];
+        board.grid[bottom - 1] = [Some(Colors::Blue); GAME_SIZE.x];
+        board.grid[bottom - 1][0] = None;
+        board.grid[bottom - 2] = [Some(Colors::Green); GAME_SIZE.x];
+        board.grid[bottom - 3][4] = Some(Colors::Yellow);
+
+        assert_eq!(board.clear_lines(), Some(LineClear::Double));
+        assert!(board.grid[bottom][0].is_none());
+        assert!(board.grid[bottom][1].is_some());
+        assert!(board.grid[bottom - 1][4].is_some());
+        let filled = board.grid.iter().flatten().filter(|c| c.is_some());
+        assert_eq!(filled.count(), GAME_SIZE.x);
+    }
+
+    #[test]
+    fn test_clear_lines_none_when_no_full_rows() {
+        let mut board = GameBoard::new();
+        board.grid[GAME_SIZE.y - 1][0] = Some(Colors::Red);
+        assert_eq!(board.clear_lines(), None);
+        assert!(board.grid[GAME_SIZE.y - 1][0].is_some());
+    }
+
+    #[test]
+    fn test_rotation_without_obstruction_uses_first_kick() {
+        let mut board = GameBoard::new();
+        board.active_peice = Some(ActivePiece {
+            pos: Vec2::new(4, 10),
+            tetromino: Tetromino::new(TetrominoShape::T, TetrominoRotation::Zero),
+            last_kick: None,
+        });
+        assert!(board.try_move_active_piece(MoveDirection::CW));
+        let ap = board.active_peice.unwrap();
+        assert_eq!(ap.tetromino.rotation, TetrominoRotation::Ninety);
+        assert_eq!(ap.pos, Vec2::new(4, 10));
+        assert_eq!(ap.last_kick, Some(0));
+    }
+
+    #[test]
+    fn test_rotation_kicks_off_left_wall() {
+        let mut board = GameBoard::new();
+        // Vertical I in the second column of its box, flush with the wall
+        board.active_peice = Some(ActivePiece {
+            pos: Vec2::new(-2, 10),
+            tetromino: Tetromino::new(TetrominoShape::I, TetrominoRotation::Ninety),
+            last_kick: None,
+        });
+        assert!(board.try_move_active_piece(MoveDirection::CW));
+        let ap = board.active_peice.unwrap();
+        assert_eq!(ap.tetromino.rotation, TetrominoRotation::OneEighty);
+        // R->2 kick #2 is (+2, 0)
+        assert_eq!(ap.last_kick, Some(2));
+        assert_eq!(ap.pos, Vec2::new(0, 10));
+    }
+
+    #[test]
+    fn test_rotation_rejected_when_no_kick_fits() {
+        let mut board = GameBoard::new();
+        for row in board.grid.iter_mut() {
+            *row = [Some(Colors::Red); GAME_SIZE.x];
+            row[4] = None;
+        }
+        board.active_peice = Some(ActivePiece {
+            pos: Vec2::new(2, 10),
+            tetromino: Tetromino::new(TetrominoShape::I, TetrominoRotation::Ninety),
+            last_kick: None,
+        });
+        assert!(!board.try_move_active_piece(MoveDirection::CW));
+        assert_eq!(
+            board.active_peice.unwrap().tetromino.rotation,
+            TetrominoRotation::Ninety
+        );
+    }
+
+    #[test]
+    fn test_translation_clears_last_kick() {
+        let mut board = GameBoard::new();
+        board.active_peice = Some(ActivePiece::spawn(TetrominoShape::T));
+        assert!(board.try_move_active_piece(MoveDirection::CW));
+        assert!(board.active_peice.unwrap().last_kick.is_some());
+        assert!(board.try_move_active_piece(MoveDirection::Down));
+        assert!(board.active_peice.unwrap().last_kick.is_none());
+    }
+
+    #[test]
+    fn test_t_needs_rotation_to_spin() {
+        let board = board_from(&[
+            "##........", //
+            "#...######",
+            "##.#######",
+        ]);
+        let ap = ActivePiece {
+            pos: Vec2::new(1, GAME_SIZE.y as i32 - 3),
+            tetromino: Tetromino::new(TetrominoShape::T, TetrominoRotation::OneEighty),
+            last_kick: None,
+        };
+        assert_eq!(board.t_spin(&ap), Spin::None);
+        let ap = ActivePiece {
+            last_kick: Some(0),
+            ..ap
+        };
+        assert_eq!(board.t_spin(&ap), Spin::Full);
+    }
+
+    #[test]
+    fn test_t_spin_mini_against_wall() {
+        let board = board_from(&[
+            "..#.......", //
+            "..########",
+            "#.########",
+        ]);
+        // T pointing left, only one of the corners it points at is filled
+        let ap = ActivePiece {
+            pos: Vec2::new(0, GAME_SIZE.y as i32 - 3),
+            tetromino: Tetromino::new(TetrominoShape::T, TetrominoRotation::TwoSeventy),
+            last_kick: Some(1),
+        };
+        assert!(board.fits(ap.pos, ap.tetromino));
+        assert_eq!(board.t_spin(&ap), Spin::Mini);
+        let ap = ActivePiece {
+            last_kick: Some(4),
+            ..ap
+        };
+        assert_eq!(board.t_spin(&ap), Spin::Full);
+    }
+}
@@ -0,0 +1,922 @@
+use std::collections::VecDeque;
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+use crate::board::{ActivePiece, GameBoard, LineClear};
+use crate::gravity::{self, GravityAccumulator, GravityCurve};
+use crate::input::{AutoShift, Handling, Shift};
+use crate::lock_delay::{LockDelay, LockResetPolicy};
+use crate::randomizer::{Randomizer, RandomizerKind};
+use crate::scoring::{Award, Scoring, Spin};
+use crate::tetromino::{MoveDirection, TetrominoShape};
+
+/// Something the player can do to the piece in play
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    Move(MoveDirection),
+    HardDrop,
+    Hold,
+    /// Locks the piece where it is, a debugging aid
+    Lock,
+}
+
+/// A key going down or coming back up. Left, right and soft drop keep acting
+/// until released, a frontend that can't see releases should send a release
+/// straight after every press
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Input {
+    Press(Command),
+    Release(Command),
+}
+
+/// How the game ended
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TopOut {
+    /// A new piece overlapped the stack as it spawned
+    BlockOut,
+    /// A piece locked without reaching the playfield
+    LockOut,
+}
+
+/// Something that happened during a step, in the order it happened. Enough
+/// to follow a game without looking at the board
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    /// A piece entered play, from the queue or out of hold
+    PieceSpawned(TetrominoShape),
+    /// The piece in play locked into the stack. `award` covers everything the
+    /// lock scored, drop points aside
+    PieceLocked { shape: TetrominoShape, award: Award },
+    /// The last lock completed rows. `combo` is 0 for the first clear in a
+    /// row of clearing locks
+    LinesCleared {
+        count: usize,
+        spin: Spin,
+        b2b: bool,
+        combo: usize,
+        perfect_clear: bool,
+    },
+    /// The level went up to this one
+    LevelUp(usize),
+    /// This shape went into the hold slot
+    Hold(TetrominoShape),
+    /// The game is over, no more events follow
+    TopOut(TopOut),
+    /// The goal was reached, no more events follow
+    Finished,
+}
+
+pub type Events = Vec<Event>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameState {
+    Playing,
+    GameOver,
+    /// The goal was reached before topping out
+    Finished,
+}
+
+/// What ends a game other than topping out
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Goal {
+    #[default]
+    Endless,
+    /// Finish on the lock that clears this many lines in total
+    Lines(usize),
+    /// Finish once this many frames have been played
+    Frames(u64),
+}
+
+/// Everything that decides how a game plays out, fixed for its whole length
+#[derive(Clone, Debug)]
+pub struct GameOptions {
+    pub randomizer: RandomizerKind,
+    /// Every game is seeded, this just pins the seed rather than picking a
+    /// fresh one each game
+    pub seed: Option<u64>,
+    /// How many upcoming pieces are dealt ahead of the one in play
+    pub preview_count: usize,
+    pub lock_reset: LockResetPolicy,
+    pub handling: Handling,
+    pub gravity: GravityCurve,
+    pub lines_per_level: usize,
+    /// The level the game starts on, counting up from there
+    pub start_level: usize,
+    pub goal: Goal,
+}
+impl Default for GameOptions {
+    fn default() -> Self {
+        GameOptions {
+            randomizer: RandomizerKind::default(),
+            seed: None,
+            preview_count: 5,
+            lock_reset: LockResetPolicy::default(),
+            handling: Handling::default(),
+            gravity: GravityCurve::default(),
+            lines_per_level: 10,
+            start_level: 1,
+            goal: Goal::default(),
+        }
+    }
+}
+
+/// A single game from first piece to top out
+pub struct Game {
+    options: GameOptions,
+    // All randomness in a game comes from this one rng, so a seed and the
+    // same inputs always play out the same
+    seed: u64,
+    rng: ChaCha8Rng,
+    randomizer: Box<dyn Randomizer>,
+    next_queue: VecDeque<TetrominoShape>,
+    held: Option<TetrominoShape>,
+    // Set once the player holds, cleared when the next piece locks
+    hold_used: bool,
+    lock_delay: LockDelay,
+    auto_shift: AutoShift,
+    soft_drop_held: bool,
+    gravity: GravityAccumulator,
+    board: GameBoard,
+    state: GameState,
+    scoring: Scoring,
+    lines: usize,
+    level: usize,
+    // Logic frames played, stops counting at game over
+    frame: u64,
+    // Collected over a step and handed back at the end of it
+    events: Events,
+}
+
+impl Game {
+    /// Starts a game with the first piece already in play
+    pub fn new(mut options: GameOptions) -> Game {
+        // Zero would never level up, and divide by zero besides
+        options.lines_per_level = options.lines_per_level.max(1);
+        let seed = options.seed.unwrap_or_else(rand::random);
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let mut randomizer = options.randomizer.build();
+        let next_queue = (0..options.preview_count)
+            .map(|_| randomizer.next_shape(&mut rng))
+            .collect();
+        let level = options.start_level.max(1);
+        let mut game = Game {
+            seed,
+            rng,
+            randomizer,
+            next_queue,
+            held: None,
+            hold_used: false,
+            lock_delay: LockDelay::new(options.lock_reset),
+            auto_shift: AutoShift::new(options.handling),
+            soft_drop_held: false,
+            gravity: GravityAccumulator::default(),
+            options,
+            board: GameBoard::new(),
+            state: GameState::Playing,
+            scoring: Scoring::default(),
+            lines: 0,
+            level,
+            frame: 0,
+            events: Events::new(),
+        };
+        // The first piece's spawn is reported by the first step
+        game.spawn_piece();
+        game
+    }
+
+    pub fn options(&self) -> &GameOptions {
+        &self.options
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn board(&self) -> &GameBoard {
+        &self.board
+    }
+
+    /// The upcoming pieces, soonest first
+    pub fn next_queue(&self) -> &VecDeque<TetrominoShape> {
+        &self.next_queue
+    }
+
+    pub fn held(&self) -> Option<TetrominoShape> {
+        self.held
+    }
+
+    /// Whether hold has been used up for the piece in play
+    pub fn hold_used(&self) -> bool {
+        self.hold_used
+    }
+
+    pub fn state(&self) -> GameState {
+        self.state
+    }
+
+    pub fn score(&self) -> usize {
+        self.scoring.score
+    }
+
+    pub fn lines(&self) -> usize {
+        self.lines
+    }
+
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Game time, measured in frames so it's identical on every replay
+    pub fn elapsed(&self) -> std::time::Duration {
+        Game::frame_time(self.frame)
+    }
+
+    /// How long `frames` logic frames last, to the millisecond
+    pub fn frame_time(frames: u64) -> std::time::Duration {
+        std::time::Duration::from_millis(frames * 1000 / u64::from(gravity::FRAME_RATE))
+    }
+
+    fn game_over(&mut self, top_out: TopOut) {
+        self.state = GameState::GameOver;
+        self.events.push(Event::TopOut(top_out));
+    }
+
+    fn finish(&mut self) {
+        self.state = GameState::Finished;
+        self.events.push(Event::Finished);
+    }
+
+    fn press(&mut self, command: Command) {
+        match command {
+            Command::Lock => self.lock_and_spawn(),
+            Command::Move(MoveDirection::SonicDrop) => {
+                let distance = self.sonic_drop();
+                self.scoring.soft_drop(distance);
+            }
+            Command::Move(direction) => {
+                match direction {
+                    MoveDirection::Left => self.auto_shift.press(Shift::Left),
+                    MoveDirection::Right => self.auto_shift.press(Shift::Right),
+                    MoveDirection::Down => self.soft_drop_held = true,
+                    _ => {}
+                }
+                let is_down = matches!(direction, MoveDirection::Down);
+                if self.move_piece(direction) && is_down {
+                    self.scoring.soft_drop(1);
+                    self.gravity.reset();
+                }
+            }
+            Command::HardDrop => self.hard_drop(),
+            Command::Hold => self.hold(),
+        }
+    }
+
+    fn release(&mut self, command: Command) {
+        match command {
+            Command::Move(MoveDirection::Left) => self.auto_shift.release(Shift::Left),
+            Command::Move(MoveDirection::Right) => self.auto_shift.release(Shift::Right),
+            Command::Move(MoveDirection::Down) => self.soft_drop_held = false,
+            _ => {}
+        }
+    }
+
+    // Moves the piece for however many auto repeats are due
+    fn auto_repeat(&mut self) -> bool {
+        let repeats = self.auto_shift.tick();
+        let direction = match self.auto_shift.direction() {
+            Some(Shift::Left) => MoveDirection::Left,
+            Some(Shift::Right) => MoveDirection::Right,
+            None => return false,
+        };
+        let mut moved = false;
+        for _ in 0..repeats {
+            if !self.move_piece(direction) {
+                break;
+            }
+            moved = true;
+        }
+        moved
+    }
+
+    // Spawns a new piece at the top of the board, topping out (block out) if
+    // it overlaps the stack
+    fn spawn_piece(&mut self) {
+        self.next_queue
+            .push_back(self.randomizer.next_shape(&mut self.rng));
+        let shape = self.next_queue.pop_front().unwrap();
+        self.spawn_shape(shape);
+    }
+
+    fn spawn_shape(&mut self, shape: TetrominoShape) {
+        let new_ap = ActivePiece::spawn(shape);
+        self.lock_delay.on_spawn(new_ap.pos.y);
+        self.gravity.reset();
+        if self.board.fits(new_ap.pos, new_ap.tetromino) {
+            self.events.push(Event::PieceSpawned(shape));
+        } else {
+            self.game_over(TopOut::BlockOut);
+        }
+        self.board.active_peice = Some(new_ap);
+    }
+
+    // Swaps the active piece into the hold slot, once per piece
+    fn hold(&mut self) {
+        let Some(ap) = self.board.active_peice else {
+            return;
+        };
+        if self.hold_used {
+            return;
+        }
+        self.hold_used = true;
+        self.events.push(Event::Hold(ap.tetromino.shape));
+        match self.held.replace(ap.tetromino.shape) {
+            Some(shape) => self.spawn_shape(shape),
+            None => self.spawn_piece(),
+        }
+    }
+
+    // Player initiated move, which may buy the piece more time before locking
+    fn move_piece(&mut self, direction: MoveDirection) -> bool {
+        if !self.board.try_move_active_piece(direction) {
+            return false;
+        }
+        if let Some(ap) = self.board.active_peice {
+            self.lock_delay.on_move(ap.pos.y);
+        }
+        true
+    }
+
+    // Gravity pulling the piece down a row, if there is room
+    fn fall(&mut self) -> bool {
+        if !self.board.try_move_active_piece(MoveDirection::Down) {
+            return false;
+        }
+        if let Some(ap) = self.board.active_peice {
+            self.lock_delay.on_descend(ap.pos.y);
+        }
+        true
+    }
+
+    // Current gravity in rows per frame, soft drop included
+    fn gravity_g(&self) -> f64 {
+        let g = self.options.gravity.rows_per_frame(self.level);
+        if self.soft_drop_held {
+            (g * self.options.handling.sdf as f64).min(gravity::MAX_GRAVITY)
+        } else {
+            g
+        }
+    }
+
+    // Drops the piece however many rows of gravity are due this frame
+    fn apply_gravity(&mut self) -> bool {
+        let rows = self.gravity.advance(self.gravity_g());
+        let mut moved = false;
+        for _ in 0..rows {
+            if !self.fall() {
+                // Resting on the stack, gravity doesn't bank up
+                self.gravity.reset();
+                break;
+            }
+            if self.soft_drop_held {
+                self.scoring.soft_drop(1);
+            }
+            moved = true;
+        }
+        moved
+    }
+
+    fn is_grounded(&self) -> bool {
+        self.board
+            .active_peice
+            .is_some_and(|ap| self.board.drop_distance(ap.pos, ap.tetromino) == 0)
+    }
+
+    // Drops the active piece straight down, returning how many rows it fell
+    fn sonic_drop(&mut self) -> usize {
+        let Some(ap) = self.board.active_peice else {
+            return 0;
+        };
+        let distance = self.board.drop_distance(ap.pos, ap.tetromino);
+        self.move_piece(MoveDirection::SonicDrop);
+        distance as usize
+    }
+
+    fn hard_drop(&mut self) {
+        let distance = self.sonic_drop();
+        self.scoring.hard_drop(distance);
+        self.lock_and_spawn();
+    }
+
+    // Logic for making the current piece static and making a new one
+    fn lock_and_spawn(&mut self) {
+        if let Some(ap) = self.board.active_peice {
+            let spin = self.board.t_spin(&ap);
+            self.board.draw_to_board(ap.pos, ap.tetromino);
+            self.board.active_peice = None;
+            self.hold_used = false;
+            // Lock out, the piece never made it down into the playfield
+            if GameBoard::is_above_playfield(ap.pos, ap.tetromino) {
+                self.game_over(TopOut::LockOut);
+                return;
+            }
+            let lines = self.board.clear_lines().map_or(0, LineClear::count);
+            self.lines += lines;
+            let perfect_clear = self.board.grid.iter().flatten().all(|c| c.is_none());
+            // Scored at the level the clear happened on, then level up
+            let award = self.scoring.lock(lines, spin, perfect_clear, self.level);
+            self.events.push(Event::PieceLocked {
+                shape: ap.tetromino.shape,
+                award,
+            });
+            if lines > 0 {
+                self.events.push(Event::LinesCleared {
+                    count: lines,
+                    spin,
+                    b2b: award.back_to_back,
+                    combo: award.combo.unwrap_or(0),
+                    perfect_clear,
+                });
+            }
+            let level = self.options.start_level.max(1) + self.lines / self.options.lines_per_level;
+            if level > self.level {
+                self.level = level;
+                self.events.push(Event::LevelUp(level));
+            }
+            if let Goal::Lines(goal) = self.options.goal {
+                if self.lines >= goal {
+                    self.finish();
+                    return;
+                }
+            }
+            self.spawn_piece();
+        }
+    }
+
+    /// Advances the game by exactly one logic frame, applying `inputs` at the
+    /// start of it. Everything that happens in play is a function of the seed
+    /// and the inputs given to each step
+    pub fn step(&mut self, inputs: &[Input]) -> Events {
+        for input in inputs {
+            if self.state != GameState::Playing {
+                break;
+            }
+            match *input {
+                Input::Press(command) => self.press(command),
+                Input::Release(command) => self.release(command),
+            }
+        }
+
+        if self.state == GameState::Playing {
+            self.frame += 1;
+
+            self.auto_repeat();
+
+            // Gravity handling, sped up while soft drop is held
+            self.apply_gravity();
+
+            // Lock delay, runs while the piece is resting on the stack
+            if self.lock_delay.tick(self.is_grounded()) {
+                self.lock_and_spawn();
+            }
+
+            if let Goal::Frames(goal) = self.options.goal {
+                if self.frame >= goal && self.state == GameState::Playing {
+                    self.finish();
+                }
+            }
+        }
+        std::mem::take(&mut self.events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{board_from, GAME_SIZE, SPAWN_POS};
+    use crate::lock_delay;
+    use crate::tetromino::{Colors, Tetromino, TetrominoRotation};
+    use crate::utils::Vec2;
+
+    // Four rows with a well on the right and a vertical I above it at `y`,
+    // ready to lock for a tetris
+    fn tetris_ready(y: i32) -> GameBoard {
+        let mut board = board_from(&[
+            "#########.",
+            "#########.",
+            "#########.",
+            "#########.",
+            "#.########",
+        ]);
+        board.active_peice = Some(ActivePiece {
+            pos: Vec2::new(7, y),
+            tetromino: Tetromino::new(TetrominoShape::I, TetrominoRotation::Ninety),
+            last_kick: None,
+        });
+        board
+    }
+
+    #[test]
+    fn test_lock_and_spawn_draws_piece_and_respawns() {
+        let mut game = Game::new(GameOptions::default());
+        game.board.active_peice = Some(ActivePiece::spawn(TetrominoShape::O));
+        while game.board.try_move_active_piece(MoveDirection::Down) {}
+        game.lock_and_spawn();
+        let filled = game.board.grid.iter().flatten().filter(|c| c.is_some());
+        assert_eq!(filled.count(), 4);
+        assert_eq!(game.board.active_peice.unwrap().pos, SPAWN_POS);
+    }
+
+    #[test]
+    fn test_block_out_ends_game() {
+        let mut game = Game::new(GameOptions::default());
+        game.board.grid[1] = [Some(Colors::Red); GAME_SIZE.x];
+        game.board.grid[1][0] = None;
+        game.spawn_piece();
+        assert_eq!(game.state, GameState::GameOver);
+    }
+
+    #[test]
+    fn test_lock_out_ends_game() {
+        let mut game = Game::new(GameOptions::default());
+        game.board.active_peice = Some(ActivePiece::spawn(TetrominoShape::O));
+        game.lock_and_spawn();
+        assert_eq!(game.state, GameState::GameOver);
+    }
+
+    #[test]
+    fn test_lock_inside_playfield_keeps_playing() {
+        let mut game = Game::new(GameOptions::default());
+        game.board.active_peice = Some(ActivePiece::spawn(TetrominoShape::O));
+        while game.board.try_move_active_piece(MoveDirection::Down) {}
+        game.lock_and_spawn();
+        assert_eq!(game.state, GameState::Playing);
+    }
+
+    #[test]
+    fn test_sonic_drop_lands_without_locking() {
+        let mut game = Game::new(GameOptions::default());
+        game.board.active_peice = Some(ActivePiece::spawn(TetrominoShape::O));
+        assert_eq!(game.sonic_drop(), GAME_SIZE.y - 2);
+        assert_eq!(
+            game.board.active_peice.unwrap().pos.y,
+            GAME_SIZE.y as i32 - 2
+        );
+        assert!(game.board.grid.iter().flatten().all(|c| c.is_none()));
+    }
+
+    #[test]
+    fn test_hard_drop_locks_and_scores_distance() {
+        let mut game = Game::new(GameOptions::default());
+        game.board.active_peice = Some(ActivePiece::spawn(TetrominoShape::O));
+        game.hard_drop();
+        assert_eq!(game.scoring.score, 2 * (GAME_SIZE.y - 2));
+        assert!(game.board.grid[GAME_SIZE.y - 1][SPAWN_POS.x as usize].is_some());
+        assert_eq!(game.board.active_peice.unwrap().pos, SPAWN_POS);
+    }
+
+    #[test]
+    fn test_spawn_takes_from_front_of_next_queue() {
+        let mut game = Game::new(GameOptions {
+            preview_count: 3,
+            ..Default::default()
+        });
+        let upcoming: Vec<_> = game.next_queue.iter().copied().collect();
+        assert_eq!(upcoming.len(), 3);
+        game.spawn_piece();
+        let ap = game.board.active_peice.unwrap();
+        assert_eq!(ap.tetromino.shape, upcoming[0]);
+        assert_eq!(game.next_queue.len(), 3);
+        assert_eq!(game.next_queue[0], upcoming[1]);
+        assert_eq!(game.next_queue[1], upcoming[2]);
+    }
+
+    #[test]
+    fn test_same_seed_deals_same_pieces() {
+        let deal = |seed| {
+            let mut game = Game::new(GameOptions {
+                randomizer: RandomizerKind::PureRandom,
+                seed: Some(seed),
+                ..Default::default()
+            });
+            (0..50)
+                .map(|_| game.randomizer.next_shape(&mut game.rng))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(deal(1234), deal(1234));
+        assert_ne!(deal(1234), deal(4321));
+    }
+
+    #[test]
+    fn test_hold_into_empty_slot_takes_next_piece() {
+        let mut game = Game::new(GameOptions::default());
+        let first = game.board.active_peice.unwrap().tetromino.shape;
+        let next = game.next_queue[0];
+        game.hold();
+        assert_eq!(game.held, Some(first));
+        assert_eq!(game.board.active_peice.unwrap().tetromino.shape, next);
+    }
+
+    #[test]
+    fn test_hold_once_per_piece() {
+        let mut game = Game::new(GameOptions::default());
+        let first = game.board.active_peice.unwrap().tetromino.shape;
+        game.hold();
+        let second = game.board.active_peice.unwrap().tetromino.shape;
+        game.hold();
+        assert_eq!(game.held, Some(first));
+        assert_eq!(game.board.active_peice.unwrap().tetromino.shape, second);
+
+        game.hard_drop();
+        assert!(!game.hold_used);
+        let third = game.board.active_peice.unwrap().tetromino.shape;
+        game.hold();
+        assert_eq!(game.held, Some(third));
+        let ap = game.board.active_peice.unwrap();
+        assert_eq!(ap.tetromino.shape, first);
+        assert_eq!(ap.pos, SPAWN_POS);
+    }
+
+    #[test]
+    fn test_landed_piece_waits_for_lock_delay() {
+        let mut game = Game::new(GameOptions::default());
+        game.spawn_shape(TetrominoShape::O);
+        game.sonic_drop();
+        assert!(game.is_grounded());
+        // Gravity alone no longer locks the piece
+        game.fall();
+        assert!(game.board.grid.iter().flatten().all(|c| c.is_none()));
+        for _ in 1..lock_delay::LOCK_DELAY_FRAMES {
+            assert!(!game.lock_delay.tick(game.is_grounded()));
+        }
+        assert!(game.lock_delay.tick(game.is_grounded()));
+    }
+
+    #[test]
+    fn test_sonic_drop_in_place_keeps_lock_timer() {
+        let mut game = Game::new(GameOptions::default());
+        game.spawn_shape(TetrominoShape::O);
+        game.sonic_drop();
+        for _ in 1..lock_delay::LOCK_DELAY_FRAMES {
+            assert!(!game.lock_delay.tick(game.is_grounded()));
+        }
+        // Already on the stack, this moves nothing and resets nothing
+        assert_eq!(game.sonic_drop(), 0);
+        assert!(!game.move_piece(MoveDirection::SonicDrop));
+        assert!(game.lock_delay.tick(game.is_grounded()));
+    }
+
+    #[test]
+    fn test_auto_repeat_slides_piece_to_wall() {
+        let mut game = Game::new(GameOptions {
+            handling: Handling {
+                arr: 0,
+                ..Handling::default()
+            },
+            ..Default::default()
+        });
+        game.spawn_shape(TetrominoShape::O);
+        game.auto_shift.press(Shift::Left);
+        for _ in 1..game.options.handling.das {
+            assert!(!game.auto_repeat());
+        }
+        assert!(game.auto_repeat());
+        assert_eq!(game.board.active_peice.unwrap().pos.x, 0);
+
+        game.release(Command::Move(MoveDirection::Left));
+        assert!(!game.auto_repeat());
+    }
+
+    #[test]
+    fn test_t_spin_double_scores_through_lock() {
+        let mut game = Game::new(GameOptions::default());
+        game.board = board_from(&[
+            "##........", //
+            "#...######",
+            "##.#######",
+        ]);
+        game.board.active_peice = Some(ActivePiece {
+            pos: Vec2::new(1, GAME_SIZE.y as i32 - 3),
+            tetromino: Tetromino::new(TetrominoShape::T, TetrominoRotation::OneEighty),
+            last_kick: Some(0),
+        });
+        game.lock_and_spawn();
+        assert_eq!(game.lines, 2);
+        assert_eq!(game.scoring.score, 1200);
+    }
+
+    #[test]
+    fn test_t_spin_double_scores_through_hard_drop() {
+        let mut game = Game::new(GameOptions::default());
+        game.step(&[]);
+        game.board = board_from(&[
+            "##........", //
+            "#...######",
+            "##.#######",
+        ]);
+        game.board.active_peice = Some(ActivePiece {
+            pos: Vec2::new(1, GAME_SIZE.y as i32 - 3),
+            tetromino: Tetromino::new(TetrominoShape::T, TetrominoRotation::OneEighty),
+            last_kick: Some(0),
+        });
+        // Already resting, so the drop covers no distance and keeps the kick
+        game.step(&[Input::Press(Command::HardDrop)]);
+        assert_eq!(game.lines, 2);
+        assert_eq!(game.scoring.score, 1200);
+    }
+
+    #[test]
+    fn test_level_up_every_ten_lines() {
+        let mut game = Game::new(GameOptions::default());
+        for _ in 0..3 {
+            game.board = tetris_ready(GAME_SIZE.y as i32 - 5);
+            game.lock_and_spawn();
+        }
+        assert_eq!(game.lines, 12);
+        assert_eq!(game.level, 2);
+        // The third tetris was still scored at level 1: tetris, then two back
+        // to back tetrises with combo bonuses
+        assert_eq!(game.scoring.score, 800 + (1200 + 50) + (1200 + 100));
+    }
+
+    #[test]
+    fn test_zero_lines_per_level_is_treated_as_one() {
+        let mut game = Game::new(GameOptions {
+            lines_per_level: 0,
+            ..Default::default()
+        });
+        game.board = board_from(&["#########."]);
+        game.board.active_peice = Some(ActivePiece {
+            pos: Vec2::new(7, GAME_SIZE.y as i32 - 4),
+            tetromino: Tetromino::new(TetrominoShape::I, TetrominoRotation::Ninety),
+            last_kick: None,
+        });
+        game.lock_and_spawn();
+        assert_eq!(game.level(), 2);
+    }
+
+    #[test]
+    fn test_twenty_g_lands_immediately() {
+        let mut game = Game::new(GameOptions {
+            gravity: GravityCurve::TwentyG,
+            ..Default::default()
+        });
+        game.spawn_shape(TetrominoShape::O);
+        assert!(game.apply_gravity());
+        assert!(game.is_grounded());
+    }
+
+    #[test]
+    fn test_level_one_falls_a_row_per_second() {
+        let mut game = Game::new(GameOptions::default());
+        let y = game.board.active_peice.unwrap().pos.y;
+        for _ in 0..gravity::FRAME_RATE {
+            game.step(&[]);
+        }
+        assert_eq!(game.board.active_peice.unwrap().pos.y, y + 1);
+        assert_eq!(game.elapsed(), std::time::Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_same_seed_and_inputs_replay_identically() {
+        let play = || {
+            let mut game = Game::new(GameOptions {
+                seed: Some(7),
+                ..Default::default()
+            });
+            let left = Command::Move(MoveDirection::Left);
+            for frame in 0..2000 {
+                let inputs: &[Input] = match frame % 90 {
+                    0 => &[Input::Press(Command::Move(MoveDirection::CW))],
+                    30 => &[Input::Press(left)],
+                    40 => &[Input::Release(left)],
+                    45 => &[Input::Press(Command::HardDrop)],
+                    _ => &[],
+                };
+                game.step(inputs);
+            }
+            let cells = game.board.grid.map(|row| row.map(|cell| cell.is_some()));
+            (cells, game.scoring.score, game.frame)
+        };
+        assert_eq!(play(), play());
+    }
+
+    #[test]
+    fn test_first_step_reports_first_spawn() {
+        let mut game = Game::new(GameOptions::default());
+        let shape = game.board.active_peice.unwrap().tetromino.shape;
+        assert_eq!(game.step(&[]), vec![Event::PieceSpawned(shape)]);
+        assert!(game.step(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_step_reports_lock_out_once() {
+        let mut game = Game::new(GameOptions::default());
+        game.step(&[]);
+        // Locking straight away never leaves the buffer rows
+        let events = game.step(&[Input::Press(Command::Lock)]);
+        assert_eq!(events, vec![Event::TopOut(TopOut::LockOut)]);
+        assert_eq!(game.state(), GameState::GameOver);
+        assert!(game.step(&[Input::Press(Command::HardDrop)]).is_empty());
+    }
+
+    #[test]
+    fn test_block_out_reported_instead_of_spawn() {
+        let mut game = Game::new(GameOptions::default());
+        game.events.clear();
+        game.board.grid[1] = [Some(Colors::Red); GAME_SIZE.x];
+        game.spawn_piece();
+        assert_eq!(game.events, vec![Event::TopOut(TopOut::BlockOut)]);
+    }
+
+    #[test]
+    fn test_hold_reports_hold_then_spawn() {
+        let mut game = Game::new(GameOptions::default());
+        game.step(&[]);
+        let first = game.board.active_peice.unwrap().tetromino.shape;
+        let next = game.next_queue[0];
+        let events = game.step(&[Input::Press(Command::Hold)]);
+        assert_eq!(events, vec![Event::Hold(first), Event::PieceSpawned(next)]);
+    }
+
+    #[test]
+    fn test_tetris_reports_lock_clear_and_level_up_in_order() {
+        let mut game = Game::new(GameOptions {
+            lines_per_level: 4,
+            ..Default::default()
+        });
+        game.step(&[]);
+        game.board = tetris_ready(0);
+        let next = game.next_queue[0];
+        let events = game.step(&[Input::Press(Command::HardDrop)]);
+        let [Event::PieceLocked { shape, award }, cleared, level_up, spawned] = events[..] else {
+            panic!("unexpected events {:?}", events);
+        };
+        assert_eq!(shape, TetrominoShape::I);
+        assert_eq!(award.points, 800);
+        assert_eq!(
+            cleared,
+            Event::LinesCleared {
+                count: 4,
+                spin: Spin::None,
+                b2b: false,
+                combo: 0,
+                perfect_clear: false,
+            }
+        );
+        assert_eq!(level_up, Event::LevelUp(2));
+        assert_eq!(spawned, Event::PieceSpawned(next));
+    }
+
+    #[test]
+    fn test_line_goal_finishes_game() {
+        let mut game = Game::new(GameOptions {
+            goal: Goal::Lines(4),
+            ..Default::default()
+        });
+        game.step(&[]);
+        game.board = tetris_ready(0);
+        let events = game.step(&[Input::Press(Command::HardDrop)]);
+        assert_eq!(events.last(), Some(&Event::Finished));
+        assert_eq!(game.state(), GameState::Finished);
+        assert!(game.board.active_peice.is_none());
+        // The clock stops with the game
+        let frame = game.frame();
+        assert!(game.step(&[]).is_empty());
+        assert_eq!(game.frame(), frame);
+    }
+
+    #[test]
+    fn test_time_goal_finishes_game() {
+        let mut game = Game::new(GameOptions {
+            goal: Goal::Frames(gravity::FRAME_RATE.into()),
+            ..Default::default()
+        });
+        for _ in 1..gravity::FRAME_RATE {
+            game.step(&[]);
+        }
+        assert_eq!(game.state(), GameState::Playing);
+        assert_eq!(game.step(&[]), vec![Event::Finished]);
+        assert_eq!(game.state(), GameState::Finished);
+        assert_eq!(game.elapsed(), std::time::Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_start_level_counts_up_from_there() {
+        let mut game = Game::new(GameOptions {
+            start_level: 5,
+            ..Default::default()
+        });
+        assert_eq!(game.level(), 5);
+        for _ in 0..3 {
+            game.board = tetris_ready(GAME_SIZE.y as i32 - 5);
+            game.lock_and_spawn();
+        }
+        assert_eq!(game.level(), 6);
+        assert!(game.events.contains(&Event::LevelUp(6)));
+    }
+}
@@ -0,0 +1,135 @@
+/// Logic frames per second. Gravity, handling and lock delay are all counted
+/// in these
+pub const FRAME_RATE: u32 = 60;
+
+/// Every cell of the board at once, anything faster is meaningless
+pub const MAX_GRAVITY: f64 = 20.0;
+
+// NTSC NES frames per row, indexed by NES level (our level - 1)
+const NES_FRAMES_PER_ROW: [u32; 29] = [
+    48, 43, 38, 33, 28, 23, 18, 13, 8, 6, 5, 5, 5, 4, 4, 4, 3, 3, 3, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
+];
+
+/// How fast pieces fall at each level
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum GravityCurve {
+    /// Tetris Worlds formula, (0.8 - (level - 1) * 0.007)^(level - 1) seconds per row
+    #[default]
+    Guideline,
+    /// The NES frame table, 1 row per frame from level 30 on
+    Nes,
+    /// Pieces land the moment they spawn, at every level
+    TwentyG,
+}
+impl GravityCurve {
+    pub const ALL: [GravityCurve; 3] = [
+        GravityCurve::Guideline,
+        GravityCurve::Nes,
+        GravityCurve::TwentyG,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GravityCurve::Guideline => "guideline",
+            GravityCurve::Nes => "nes",
+            GravityCurve::TwentyG => "20g",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<GravityCurve> {
+        GravityCurve::ALL
+            .into_iter()
+            .find(|curve| curve.name() == name)
+    }
+
+    /// Gravity in G, rows per frame. Values above 1 drop several rows a frame
+    pub fn rows_per_frame(self, level: usize) -> f64 {
+        let level = level.max(1);
+        let g = match self {
+            GravityCurve::Guideline => {
+                // The formula is only meant up to level 20, past ~115 it goes
+                // negative
+                let n = (level.min(20) - 1) as f64;
+                let seconds_per_row = (0.8 - n * 0.007).powf(n);
+                1.0 / (seconds_per_row * f64::from(FRAME_RATE))
+            }
+            GravityCurve::Nes => {
+                let frames = NES_FRAMES_PER_ROW.get(level - 1).copied().unwrap_or(1);
+                1.0 / frames as f64
+            }
+            GravityCurve::TwentyG => MAX_GRAVITY,
+        };
+        g.min(MAX_GRAVITY)
+    }
+}
+
+/// Accumulates fractional rows of gravity until whole rows are due
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GravityAccumulator {
+    rows: f64,
+}
+impl GravityAccumulator {
+    /// Adds one frame of gravity `g`, returning how many whole rows are due
+    pub fn advance(&mut self, g: f64) -> usize {
+        self.rows += g;
+        let due = self.rows.floor();
+        self.rows -= due;
+        due as usize
+    }
+
+    pub fn reset(&mut self) {
+        self.rows = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guideline_level_one_is_one_second() {
+        let g = GravityCurve::Guideline.rows_per_frame(1);
+        assert!((g - 1.0 / 60.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_guideline_speeds_up_and_caps() {
+        let mut previous = 0.0;
+        for level in 1..=200 {
+            let g = GravityCurve::Guideline.rows_per_frame(level);
+            assert!(g >= previous);
+            assert!(g <= MAX_GRAVITY);
+            previous = g;
+        }
+        // Level 15 is just over a row per frame
+        assert!(GravityCurve::Guideline.rows_per_frame(15) > 1.0);
+    }
+
+    #[test]
+    fn test_nes_table() {
+        assert_eq!(GravityCurve::Nes.rows_per_frame(1), 1.0 / 48.0);
+        assert_eq!(GravityCurve::Nes.rows_per_frame(10), 1.0 / 6.0);
+        assert_eq!(GravityCurve::Nes.rows_per_frame(30), 1.0);
+        assert_eq!(GravityCurve::Nes.rows_per_frame(99), 1.0);
+    }
+
+    #[test]
+    fn test_accumulator_carries_fractions() {
+        let mut acc = GravityAccumulator::default();
+        for _ in 0..3 {
+            assert_eq!(acc.advance(0.25), 0);
+        }
+        assert_eq!(acc.advance(0.25), 1);
+        assert_eq!(acc.advance(MAX_GRAVITY), 20);
+        acc.advance(0.5);
+        acc.reset();
+        assert_eq!(acc.advance(0.5), 0);
+    }
+
+    #[test]
+    fn test_curve_names_round_trip() {
+        for curve in GravityCurve::ALL {
+            assert_eq!(GravityCurve::from_name(curve.name()), Some(curve));
+        }
+    }
+}
@@ -0,0 +1,148 @@
+/// How held keys repeat, in frames. Matches the settings most modern clients
+/// expose
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Handling {
+    /// Delayed auto shift, how long left/right must be held before repeating
+    pub das: u32,
+    /// Auto repeat rate, frames between repeats once DAS has charged. Zero
+    /// sends the piece straight to the wall
+    pub arr: u32,
+    /// Soft drop factor, how many times faster than gravity soft drop falls
+    pub sdf: u32,
+}
+impl Default for Handling {
+    fn default() -> Self {
+        // About 167 ms and 33 ms at 60 Hz
+        Handling {
+            das: 10,
+            arr: 2,
+            sdf: 20,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shift {
+    Left,
+    Right,
+}
+
+/// Tracks the held horizontal direction and turns frames held into repeated
+/// moves. Only usable when the terminal reports key releases
+pub struct AutoShift {
+    handling: Handling,
+    // Most recently pressed last, it wins when both are down
+    held: Vec<Shift>,
+    held_for: u32,
+}
+impl AutoShift {
+    pub fn new(handling: Handling) -> Self {
+        AutoShift {
+            handling,
+            held: Vec::with_capacity(2),
+            held_for: 0,
+        }
+    }
+
+    pub fn direction(&self) -> Option<Shift> {
+        self.held.last().copied()
+    }
+
+    /// The caller is expected to make the initial move itself
+    pub fn press(&mut self, shift: Shift) {
+        self.held.retain(|held| *held != shift);
+        self.held.push(shift);
+        self.held_for = 0;
+    }
+
+    pub fn release(&mut self, shift: Shift) {
+        let was_active = self.direction() == Some(shift);
+        self.held.retain(|held| *held != shift);
+        if was_active {
+            self.held_for = 0;
+        }
+    }
+
+    /// Advances one frame, returning how many repeat moves are now due in
+    /// `direction()`. `usize::MAX` means as far as the piece will go
+    pub fn tick(&mut self) -> usize {
+        if self.held.is_empty() {
+            return 0;
+        }
+        self.held_for += 1;
+        // The first tick is already a frame after the press, so DAS 0 charges
+        // there too rather than a frame later
+        match self.held_for.checked_sub(self.handling.das.max(1)) {
+            None => 0,
+            Some(_) if self.handling.arr == 0 => usize::MAX,
+            // The first repeat fires the moment DAS charges
+            Some(charged) => usize::from(charged % self.handling.arr == 0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HANDLING: Handling = Handling {
+        das: 6,
+        arr: 2,
+        sdf: 20,
+    };
+
+    // Total repeats due over the next `frames` frames
+    fn run(shift: &mut AutoShift, frames: u32) -> usize {
+        (0..frames)
+            .map(|_| shift.tick())
+            .fold(0, usize::saturating_add)
+    }
+
+    #[test]
+    fn test_no_repeat_before_das() {
+        let mut shift = AutoShift::new(HANDLING);
+        shift.press(Shift::Left);
+        assert_eq!(run(&mut shift, 5), 0);
+        assert_eq!(shift.tick(), 1);
+    }
+
+    #[test]
+    fn test_repeats_at_arr_once_charged() {
+        let mut shift = AutoShift::new(HANDLING);
+        shift.press(Shift::Right);
+        assert_eq!(run(&mut shift, 6), 1);
+        assert_eq!(shift.tick(), 0);
+        assert_eq!(shift.tick(), 1);
+        assert_eq!(run(&mut shift, 6), 3);
+    }
+
+    #[test]
+    fn test_zero_das_repeats_from_the_first_frame() {
+        let mut shift = AutoShift::new(Handling { das: 0, ..HANDLING });
+        shift.press(Shift::Left);
+        let repeats: Vec<usize> = (0..6).map(|_| shift.tick()).collect();
+        assert_eq!(repeats, [1, 0, 1, 0, 1, 0]);
+    }
+
+    #[test]
+    fn test_zero_arr_goes_to_wall() {
+        let mut shift = AutoShift::new(Handling { arr: 0, ..HANDLING });
+        shift.press(Shift::Left);
+        assert_eq!(run(&mut shift, 6), usize::MAX);
+    }
+
+    #[test]
+    fn test_latest_press_wins_and_release_falls_back() {
+        let mut shift = AutoShift::new(HANDLING);
+        shift.press(Shift::Left);
+        shift.press(Shift::Right);
+        assert_eq!(shift.direction(), Some(Shift::Right));
+        shift.release(Shift::Right);
+        assert_eq!(shift.direction(), Some(Shift::Left));
+        // Switching direction has to charge DAS again
+        assert_eq!(run(&mut shift, 5), 0);
+        shift.release(Shift::Left);
+        assert_eq!(shift.direction(), None);
+        assert_eq!(run(&mut shift, 30), 0);
+    }
+}
@@ -0,0 +1,17 @@
+//! Headless tetris engine. Everything needed to play a game without a
+//! terminal: the board, pieces, randomizers and rules, advanced one frame at
+//! a time through [`Game::step`]
+
+mod board;
+mod game;
+pub mod gravity;
+pub mod input;
+pub mod lock_delay;
+pub mod randomizer;
+pub mod scoring;
+pub mod tetromino;
+mod utils;
+
+pub use board::{ActivePiece, GameBoard, LineClear, BUFFER_ROWS, GAME_SIZE, SPAWN_POS};
+pub use game::{Command, Event, Events, Game, GameOptions, GameState, Goal, Input, TopOut};
+pub use utils::Vec2;
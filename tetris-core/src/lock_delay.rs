@@ -0,0 +1,186 @@
+// 500 ms at 60 Hz
+pub const LOCK_DELAY_FRAMES: u32 = 30;
+// Number of move resets allowed per row under extended placement
+pub const MOVE_RESET_LIMIT: usize = 15;
+
+/// What gives a grounded piece more time before it locks
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LockResetPolicy {
+    /// Any successful move or rotation restarts the timer
+    Infinite,
+    /// Like infinite, but only MOVE_RESET_LIMIT times before the piece has to
+    /// reach a lower row. Past the limit the piece locks as soon as it lands
+    #[default]
+    Extended,
+    /// Only reaching a lower row restarts the timer
+    Classic,
+}
+impl LockResetPolicy {
+    pub const ALL: [LockResetPolicy; 3] = [
+        LockResetPolicy::Infinite,
+        LockResetPolicy::Extended,
+        LockResetPolicy::Classic,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LockResetPolicy::Infinite => "infinite",
+            LockResetPolicy::Extended => "extended",
+            LockResetPolicy::Classic => "classic",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<LockResetPolicy> {
+        LockResetPolicy::ALL
+            .into_iter()
+            .find(|policy| policy.name() == name)
+    }
+}
+
+/// Tracks how many frames the active piece has been resting on the stack and
+/// decides when it locks
+pub struct LockDelay {
+    policy: LockResetPolicy,
+    delay: u32,
+    grounded_for: u32,
+    resets: usize,
+    lowest_row: i32,
+}
+impl LockDelay {
+    pub fn new(policy: LockResetPolicy) -> Self {
+        LockDelay {
+            policy,
+            delay: LOCK_DELAY_FRAMES,
+            grounded_for: 0,
+            resets: 0,
+            lowest_row: i32::MIN,
+        }
+    }
+
+    /// Starts over for a freshly spawned piece at row `y`
+    pub fn on_spawn(&mut self, y: i32) {
+        self.grounded_for = 0;
+        self.resets = 0;
+        self.lowest_row = y;
+    }
+
+    /// The piece moved down to row `y` on its own, i.e. gravity
+    pub fn on_descend(&mut self, y: i32) {
+        if y > self.lowest_row {
+            self.lowest_row = y;
+            self.resets = 0;
+            self.grounded_for = 0;
+        }
+    }
+
+    /// The player successfully moved or rotated the piece, which is now at row `y`
+    pub fn on_move(&mut self, y: i32) {
+        if y > self.lowest_row {
+            self.on_descend(y);
+            return;
+        }
+        match self.policy {
+            LockResetPolicy::Infinite => self.grounded_for = 0,
+            LockResetPolicy::Extended => {
+                if self.resets < MOVE_RESET_LIMIT {
+                    self.resets += 1;
+                    self.grounded_for = 0;
+                }
+            }
+            LockResetPolicy::Classic => {}
+        }
+    }
+
+    /// Advances the timer by one frame. Returns true once the piece should lock
+    pub fn tick(&mut self, grounded: bool) -> bool {
+        if !grounded {
+            self.grounded_for = 0;
+            return false;
+        }
+        self.grounded_for += 1;
+        let out_of_resets =
+            self.policy == LockResetPolicy::Extended && self.resets >= MOVE_RESET_LIMIT;
+        out_of_resets || self.grounded_for >= self.delay
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Runs `frames` frames, returning whether the piece locked on the last one
+    fn run(lock: &mut LockDelay, frames: u32, grounded: bool) -> bool {
+        (0..frames).fold(false, |_, _| lock.tick(grounded))
+    }
+
+    #[test]
+    fn test_locks_after_delay_when_grounded() {
+        let mut lock = LockDelay::new(LockResetPolicy::Extended);
+        lock.on_spawn(0);
+        assert!(!run(&mut lock, LOCK_DELAY_FRAMES - 1, true));
+        assert!(lock.tick(true));
+    }
+
+    #[test]
+    fn test_timer_does_not_run_while_airborne() {
+        let mut lock = LockDelay::new(LockResetPolicy::Extended);
+        lock.on_spawn(0);
+        assert!(!run(&mut lock, LOCK_DELAY_FRAMES * 3, false));
+        assert!(!lock.tick(true));
+    }
+
+    #[test]
+    fn test_infinite_resets_forever() {
+        let mut lock = LockDelay::new(LockResetPolicy::Infinite);
+        lock.on_spawn(5);
+        for _ in 0..100 {
+            assert!(!run(&mut lock, LOCK_DELAY_FRAMES - 1, true));
+            lock.on_move(5);
+        }
+    }
+
+    #[test]
+    fn test_extended_runs_out_of_resets() {
+        let mut lock = LockDelay::new(LockResetPolicy::Extended);
+        lock.on_spawn(5);
+        for _ in 0..MOVE_RESET_LIMIT {
+            assert!(!run(&mut lock, LOCK_DELAY_FRAMES - 1, true));
+            lock.on_move(5);
+        }
+        assert!(lock.tick(true));
+    }
+
+    #[test]
+    fn test_extended_lower_row_restores_resets() {
+        let mut lock = LockDelay::new(LockResetPolicy::Extended);
+        lock.on_spawn(5);
+        for _ in 0..MOVE_RESET_LIMIT {
+            lock.on_move(5);
+        }
+        lock.on_descend(6);
+        assert!(!lock.tick(true));
+        lock.on_move(6);
+        assert!(!run(&mut lock, LOCK_DELAY_FRAMES - 1, true));
+    }
+
+    #[test]
+    fn test_classic_ignores_moves() {
+        let mut lock = LockDelay::new(LockResetPolicy::Classic);
+        lock.on_spawn(5);
+        assert!(!run(&mut lock, LOCK_DELAY_FRAMES - 1, true));
+        lock.on_move(5);
+        assert!(lock.tick(true));
+
+        lock.on_spawn(5);
+        assert!(!run(&mut lock, LOCK_DELAY_FRAMES - 1, true));
+        lock.on_move(6);
+        assert!(!run(&mut lock, LOCK_DELAY_FRAMES - 1, true));
+    }
+
+    #[test]
+    fn test_policy_names_round_trip() {
+        for policy in LockResetPolicy::ALL {
+            assert_eq!(LockResetPolicy::from_name(policy.name()), Some(policy));
+        }
+    }
+}
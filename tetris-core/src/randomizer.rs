@@ -0,0 +1,231 @@
+use std::collections::VecDeque;
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+use rand::RngCore;
+
+use crate::tetromino::TetrominoShape;
+
+/// Decides which piece comes next. The rng is passed in rather than owned so
+/// that every source of randomness in a game can share one generator
+pub trait Randomizer {
+    fn next_shape(&mut self, rng: &mut dyn RngCore) -> TetrominoShape;
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum RandomizerKind {
+    #[default]
+    SevenBag,
+    FourteenBag,
+    PureRandom,
+    TgmHistory,
+    Nes,
+}
+impl RandomizerKind {
+    pub const ALL: [RandomizerKind; 5] = [
+        RandomizerKind::SevenBag,
+        RandomizerKind::FourteenBag,
+        RandomizerKind::PureRandom,
+        RandomizerKind::TgmHistory,
+        RandomizerKind::Nes,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RandomizerKind::SevenBag => "7bag",
+            RandomizerKind::FourteenBag => "14bag",
+            RandomizerKind::PureRandom => "random",
+            RandomizerKind::TgmHistory => "tgm",
+            RandomizerKind::Nes => "nes",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<RandomizerKind> {
+        RandomizerKind::ALL
+            .into_iter()
+            .find(|kind| kind.name() == name)
+    }
+
+    pub fn build(self) -> Box<dyn Randomizer> {
+        match self {
+            RandomizerKind::SevenBag => Box::new(Bag::new(1)),
+            RandomizerKind::FourteenBag => Box::new(Bag::new(2)),
+            RandomizerKind::PureRandom => Box::new(PureRandom),
+            RandomizerKind::TgmHistory => Box::new(TgmHistory::new(4)),
+            RandomizerKind::Nes => Box::new(Nes::new()),
+        }
+    }
+}
+
+fn random_shape(rng: &mut dyn RngCore) -> TetrominoShape {
+    TetrominoShape::ALL[rng.gen_range(0..TetrominoShape::ALL.len())]
+}
+
+/// Uniform selection with no memory, droughts and floods included
+pub struct PureRandom;
+
+impl Randomizer for PureRandom {
+    fn next_shape(&mut self, rng: &mut dyn RngCore) -> TetrominoShape {
+        random_shape(rng)
+    }
+}
+
+/// Deals out a shuffled bag holding `copies` of every shape, refilling it
+/// once empty. One copy is the guideline 7-bag, two is a 14-bag
+pub struct Bag {
+    copies: usize,
+    bag: Vec<TetrominoShape>,
+}
+impl Bag {
+    pub fn new(copies: usize) -> Self {
+        Bag {
+            copies,
+            bag: Vec::with_capacity(copies * TetrominoShape::ALL.len()),
+        }
+    }
+}
+
+impl Randomizer for Bag {
+    fn next_shape(&mut self, rng: &mut dyn RngCore) -> TetrominoShape {
+        if self.bag.is_empty() {
+            for _ in 0..self.copies {
+                self.bag.extend(TetrominoShape::ALL);
+            }
+            self.bag.shuffle(rng);
+        }
+        self.bag.pop().unwrap()
+    }
+}
+
+/// TGM style randomizer. Remembers the last four pieces and rerolls up to
+/// `rolls` times to avoid dealing one of them. The first piece is never an
+/// S, Z or O
+pub struct TgmHistory {
+    rolls: usize,
+    history: VecDeque<TetrominoShape>,
+    first: bool,
+}
+impl TgmHistory {
+    pub fn new(rolls: usize) -> Self {
+        TgmHistory {
+            rolls,
+            history: VecDeque::from([TetrominoShape::Z; 4]),
+            first: true,
+        }
+    }
+}
+
+impl Randomizer for TgmHistory {
+    fn next_shape(&mut self, rng: &mut dyn RngCore) -> TetrominoShape {
+        let shape = if self.first {
+            self.first = false;
+            const FIRST: [TetrominoShape; 4] = [
+                TetrominoShape::T,
+                TetrominoShape::L,
+                TetrominoShape::J,
+                TetrominoShape::I,
+            ];
+            FIRST[rng.gen_range(0..FIRST.len())]
+        } else {
+            let mut shape = random_shape(rng);
+            for _ in 1..self.rolls {
+                if !self.history.contains(&shape) {
+                    break;
+                }
+                shape = random_shape(rng);
+            }
+            shape
+        };
+        self.history.pop_front();
+        self.history.push_back(shape);
+        shape
+    }
+}
+
+/// NES randomizer. Rolls an eight sided die, and if it lands on the blank
+/// side or repeats the last piece, rolls a seven sided die once more and
+/// takes whatever comes up
+#[derive(Default)]
+pub struct Nes {
+    previous: Option<TetrominoShape>,
+}
+impl Nes {
+    pub fn new() -> Self {
+        Nes { previous: None }
+    }
+}
+
+impl Randomizer for Nes {
+    fn next_shape(&mut self, rng: &mut dyn RngCore) -> TetrominoShape {
+        let roll = rng.gen_range(0..=TetrominoShape::ALL.len());
+        let shape = match TetrominoShape::ALL.get(roll) {
+            Some(shape) if Some(*shape) != self.previous => *shape,
+            _ => random_shape(rng),
+        };
+        self.previous = Some(shape);
+        shape
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn deal(randomizer: &mut dyn Randomizer, count: usize) -> Vec<TetrominoShape> {
+        let mut rng = StdRng::seed_from_u64(7);
+        (0..count)
+            .map(|_| randomizer.next_shape(&mut rng))
+            .collect()
+    }
+
+    fn count_of(shapes: &[TetrominoShape], shape: TetrominoShape) -> usize {
+        shapes.iter().filter(|s| **s == shape).count()
+    }
+
+    #[test]
+    fn test_seven_bag_deals_each_shape_once_per_bag() {
+        let shapes = deal(&mut Bag::new(1), 7 * 10);
+        for bag in shapes.chunks(7) {
+            for shape in TetrominoShape::ALL {
+                assert_eq!(count_of(bag, shape), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_fourteen_bag_deals_each_shape_twice_per_bag() {
+        let shapes = deal(&mut Bag::new(2), 14 * 10);
+        for bag in shapes.chunks(14) {
+            for shape in TetrominoShape::ALL {
+                assert_eq!(count_of(bag, shape), 2);
+            }
+        }
+    }
+
+    #[test]
+    fn test_tgm_first_piece_is_never_s_z_or_o() {
+        for seed in 0..50 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let first = TgmHistory::new(4).next_shape(&mut rng);
+            assert!(![TetrominoShape::S, TetrominoShape::Z, TetrominoShape::O].contains(&first));
+        }
+    }
+
+    #[test]
+    fn test_tgm_history_makes_repeats_rarer() {
+        let shapes = deal(&mut TgmHistory::new(4), 7000);
+        let repeats = shapes.windows(2).filter(|w| w[0] == w[1]).count();
+        // Pure random would repeat roughly 1 in 7
+        assert!(repeats < 7000 / 20, "{}", repeats);
+    }
+
+    #[test]
+    fn test_kind_names_round_trip() {
+        for kind in RandomizerKind::ALL {
+            assert_eq!(RandomizerKind::from_name(kind.name()), Some(kind));
+        }
+        assert_eq!(RandomizerKind::from_name("nope"), None);
+    }
+}
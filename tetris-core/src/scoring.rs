@@ -0,0 +1,202 @@
+/// Whether the lock that's being scored was a T-spin
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Spin {
+    #[default]
+    None,
+    Mini,
+    Full,
+}
+
+/// What a single lock earned, for display and for anyone keeping stats
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Award {
+    pub points: usize,
+    pub lines: usize,
+    pub spin: Spin,
+    /// The clear was difficult and followed another difficult clear
+    pub back_to_back: bool,
+    /// Consecutive line clearing locks, 0 for the first one
+    pub combo: Option<usize>,
+    pub perfect_clear: bool,
+}
+
+impl std::fmt::Display for Award {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.back_to_back {
+            write!(f, "B2B ")?;
+        }
+        match self.spin {
+            Spin::None => {}
+            Spin::Mini => write!(f, "T-Spin Mini ")?,
+            Spin::Full => write!(f, "T-Spin ")?,
+        }
+        match self.lines {
+            0 => {}
+            1 => write!(f, "Single ")?,
+            2 => write!(f, "Double ")?,
+            3 => write!(f, "Triple ")?,
+            _ => write!(f, "Tetris ")?,
+        }
+        if self.perfect_clear {
+            write!(f, "Perfect Clear ")?;
+        }
+        if let Some(combo) = self.combo.filter(|combo| *combo > 0) {
+            write!(f, "{} combo ", combo)?;
+        }
+        write!(f, "+{}", self.points)
+    }
+}
+
+/// Guideline scoring. Line clears and spins are multiplied by level, drops
+/// are not
+#[derive(Clone, Debug, Default)]
+pub struct Scoring {
+    pub score: usize,
+    // None until a line clearing lock, reset by a lock that clears nothing
+    combo: Option<usize>,
+    // The last line clear was a tetris or a T-spin
+    back_to_back: bool,
+}
+impl Scoring {
+    pub fn soft_drop(&mut self, cells: usize) {
+        self.score += cells;
+    }
+
+    pub fn hard_drop(&mut self, cells: usize) {
+        self.score += 2 * cells;
+    }
+
+    /// Scores a piece locking with `lines` cleared
+    pub fn lock(&mut self, lines: usize, spin: Spin, perfect_clear: bool, level: usize) -> Award {
+        let base = match (spin, lines) {
+            (Spin::None, 0) => 0,
+            (Spin::None, 1) => 100,
+            (Spin::None, 2) => 300,
+            (Spin::None, 3) => 500,
+            (Spin::None, _) => 800,
+            (Spin::Mini, 0) => 100,
+            (Spin::Mini, 1) => 200,
+            (Spin::Mini, _) => 400,
+            (Spin::Full, 0) => 400,
+            (Spin::Full, 1) => 800,
+            (Spin::Full, 2) => 1200,
+            (Spin::Full, _) => 1600,
+        };
+
+        // Spins without lines don't break or extend back to back
+        let difficult = lines >= 4 || (lines > 0 && spin != Spin::None);
+        let back_to_back = difficult && self.back_to_back;
+        if lines > 0 {
+            self.back_to_back = difficult;
+        }
+        let mut points = if back_to_back { base * 3 / 2 } else { base };
+
+        self.combo = match (lines, self.combo) {
+            (0, _) => None,
+            (_, None) => Some(0),
+            (_, Some(combo)) => Some(combo + 1),
+        };
+        points += 50 * self.combo.unwrap_or(0);
+
+        if perfect_clear {
+            points += match lines {
+                1 => 800,
+                2 => 1200,
+                3 => 1800,
+                _ if back_to_back => 3200,
+                _ => 2000,
+            };
+        }
+
+        points *= level;
+        self.score += points;
+        Award {
+            points,
+            lines,
+            spin,
+            back_to_back,
+            combo: self.combo,
+            perfect_clear,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_clears_scale_with_level() {
+        let mut scoring = Scoring::default();
+        assert_eq!(scoring.lock(1, Spin::None, false, 1).points, 100);
+        assert_eq!(scoring.lock(0, Spin::None, false, 1).points, 0);
+        assert_eq!(scoring.lock(4, Spin::None, false, 3).points, 2400);
+        assert_eq!(scoring.score, 2500);
+    }
+
+    #[test]
+    fn test_back_to_back_tetris() {
+        let mut scoring = Scoring::default();
+        scoring.lock(4, Spin::None, false, 1);
+        scoring.lock(0, Spin::None, false, 1);
+        let award = scoring.lock(4, Spin::None, false, 1);
+        assert!(award.back_to_back);
+        assert_eq!(award.points, 1200);
+    }
+
+    #[test]
+    fn test_easy_clear_breaks_back_to_back_but_spin_without_lines_does_not() {
+        let mut scoring = Scoring::default();
+        scoring.lock(2, Spin::Full, false, 1);
+        scoring.lock(0, Spin::Full, false, 1);
+        assert!(scoring.lock(1, Spin::Full, false, 1).back_to_back);
+        scoring.lock(1, Spin::None, false, 1);
+        assert!(!scoring.lock(4, Spin::None, false, 1).back_to_back);
+    }
+
+    #[test]
+    fn test_t_spin_values() {
+        let mut scoring = Scoring::default();
+        assert_eq!(scoring.lock(0, Spin::Mini, false, 1).points, 100);
+        assert_eq!(scoring.lock(0, Spin::Full, false, 1).points, 400);
+        scoring = Scoring::default();
+        assert_eq!(scoring.lock(2, Spin::Full, false, 1).points, 1200);
+    }
+
+    #[test]
+    fn test_combo_counts_consecutive_clears() {
+        let mut scoring = Scoring::default();
+        assert_eq!(scoring.lock(1, Spin::None, false, 1).combo, Some(0));
+        let award = scoring.lock(1, Spin::None, false, 1);
+        assert_eq!(award.combo, Some(1));
+        assert_eq!(award.points, 150);
+        assert_eq!(scoring.lock(1, Spin::None, false, 1).points, 200);
+        assert_eq!(scoring.lock(0, Spin::None, false, 1).combo, None);
+        assert_eq!(scoring.lock(1, Spin::None, false, 1).points, 100);
+    }
+
+    #[test]
+    fn test_perfect_clear_bonus() {
+        let mut scoring = Scoring::default();
+        assert_eq!(scoring.lock(4, Spin::None, false, 1).points, 800);
+        scoring.lock(0, Spin::None, false, 1);
+        // 1200 for the b2b tetris plus 3200 for the b2b tetris perfect clear
+        assert_eq!(scoring.lock(4, Spin::None, true, 1).points, 4400);
+    }
+
+    #[test]
+    fn test_award_display() {
+        let mut scoring = Scoring::default();
+        scoring.lock(2, Spin::Full, false, 1);
+        let award = scoring.lock(2, Spin::Full, false, 1);
+        assert_eq!(award.to_string(), "B2B T-Spin Double 1 combo +1850");
+    }
+
+    #[test]
+    fn test_drops_ignore_level() {
+        let mut scoring = Scoring::default();
+        scoring.soft_drop(3);
+        scoring.hard_drop(10);
+        assert_eq!(scoring.score, 23);
+    }
+}
@@ -0,0 +1,283 @@
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Colors {
+    Purple, // T
+    Orange, // L
+    Pink,   // J
+    Red,    // Z
+    Green,  // S
+    Blue,   // I
+    Yellow, // O
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MoveDirection {
+    Down,
+    Left,
+    Right,
+    CCW,
+    CW,
+    // Straight to the bottom, without locking
+    SonicDrop,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TetrominoRotation {
+    Zero,
+    Ninety,
+    OneEighty,
+    TwoSeventy,
+}
+impl TetrominoRotation {
+    fn index(self) -> usize {
+        match self {
+            TetrominoRotation::Zero => 0,
+            TetrominoRotation::Ninety => 1,
+            TetrominoRotation::OneEighty => 2,
+            TetrominoRotation::TwoSeventy => 3,
+        }
+    }
+}
+
+// Super Rotation System wall kick offsets, as (x, y) with y pointing *up*,
+// the way the guideline writes them. Each row is one rotation transition and
+// the offsets are tried in order until one fits.
+type KickTable = [[(i32, i32); 5]; 8];
+
+// Row order: 0->R, R->0, R->2, 2->R, 2->L, L->2, L->0, 0->L
+const JLSTZ_KICKS: KickTable = [
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+];
+
+const I_KICKS: KickTable = [
+    [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)],
+    [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)],
+    [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)],
+    [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)],
+    [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)],
+    [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)],
+    [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)],
+    [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)],
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TetrominoShape {
+    T,
+    L,
+    J,
+    S,
+    Z,
+    I,
+    O,
+}
+
+impl TetrominoShape {
+    pub const ALL: [TetrominoShape; 7] = [
+        TetrominoShape::O,
+        TetrominoShape::I,
+        TetrominoShape::T,
+        TetrominoShape::S,
+        TetrominoShape::Z,
+        TetrominoShape::J,
+        TetrominoShape::L,
+    ];
+
+    // Spawn orientation (y, x), laid out in the shape's bounding box as in SRS
+    fn spawn_blocks(self) -> [(usize, usize); 4] {
+        match self {
+            TetrominoShape::T => [(0, 1), (1, 0), (1, 1), (1, 2)],
+            TetrominoShape::L => [(0, 2), (1, 0), (1, 1), (1, 2)],
+            TetrominoShape::J => [(0, 0), (1, 0), (1, 1), (1, 2)],
+            TetrominoShape::S => [(0, 1), (0, 2), (1, 0), (1, 1)],
+            TetrominoShape::Z => [(0, 0), (0, 1), (1, 1), (1, 2)],
+            TetrominoShape::I => [(1, 0), (1, 1), (1, 2), (1, 3)],
+            TetrominoShape::O => [(0, 0), (0, 1), (1, 0), (1, 1)],
+        }
+    }
+
+    // Width of the square bounding box the shape rotates within
+    fn box_size(self) -> usize {
+        match self {
+            TetrominoShape::I => 4,
+            TetrominoShape::O => 2,
+            _ => 3,
+        }
+    }
+
+    fn color(self) -> Colors {
+        match self {
+            TetrominoShape::T => Colors::Purple,
+            TetrominoShape::L => Colors::Orange,
+            TetrominoShape::J => Colors::Pink,
+            TetrominoShape::S => Colors::Green,
+            TetrominoShape::Z => Colors::Red,
+            TetrominoShape::I => Colors::Blue,
+            TetrominoShape::O => Colors::Yellow,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Tetromino {
+    // y, x
+    pub blocks: [(usize, usize); 4],
+    pub color: Colors,
+    pub shape: TetrominoShape,
+    pub rotation: TetrominoRotation,
+}
+impl Tetromino {
+    /// Builds the blocks for `rotation` by turning the spawn orientation
+    /// clockwise within its bounding box
+    pub fn new(shape: TetrominoShape, rotation: TetrominoRotation) -> Tetromino {
+        let last = shape.box_size() - 1;
+        let mut blocks = shape.spawn_blocks();
+        for _ in 0..rotation.index() {
+            blocks = blocks.map(|(y, x)| (x, last - y));
+        }
+        Tetromino {
+            blocks,
+            color: shape.color(),
+            shape,
+            rotation,
+        }
+    }
+
+    /// SRS kick offsets to try, in order, when rotating from `self` to `to`.
+    /// Offsets are (x, y) in board space, so y grows downward
+    pub fn kicks(self, to: TetrominoRotation) -> impl Iterator<Item = (i32, i32)> {
+        let row = match (self.rotation.index(), to.index()) {
+            (0, 1) => Some(0),
+            (1, 0) => Some(1),
+            (1, 2) => Some(2),
+            (2, 1) => Some(3),
+            (2, 3) => Some(4),
+            (3, 2) => Some(5),
+            (3, 0) => Some(6),
+            (0, 3) => Some(7),
+            _ => None,
+        };
+        let kicks: &'static [(i32, i32)] = match (self.shape, row) {
+            // O doesn't kick
+            (TetrominoShape::O, _) | (_, None) => &[(0, 0)],
+            (TetrominoShape::I, Some(row)) => &I_KICKS[row],
+            (_, Some(row)) => &JLSTZ_KICKS[row],
+        };
+        kicks.iter().map(|(x, y)| (*x, -*y))
+    }
+
+    pub fn rotate(self, direction: MoveDirection) -> Tetromino {
+        match (direction, self.rotation) {
+            (MoveDirection::CW, TetrominoRotation::Zero) => {
+                Tetromino::new(self.shape, TetrominoRotation::Ninety)
+            }
+            (MoveDirection::CW, TetrominoRotation::Ninety) => {
+                Tetromino::new(self.shape, TetrominoRotation::OneEighty)
+            }
+            (MoveDirection::CW, TetrominoRotation::OneEighty) => {
+                Tetromino::new(self.shape, TetrominoRotation::TwoSeventy)
+            }
+            (MoveDirection::CW, TetrominoRotation::TwoSeventy) => {
+                Tetromino::new(self.shape, TetrominoRotation::Zero)
+            }
+            (MoveDirection::CCW, TetrominoRotation::Zero) => {
+                Tetromino::new(self.shape, TetrominoRotation::TwoSeventy)
+            }
+            (MoveDirection::CCW, TetrominoRotation::Ninety) => {
+                Tetromino::new(self.shape, TetrominoRotation::Zero)
+            }
+            (MoveDirection::CCW, TetrominoRotation::OneEighty) => {
+                Tetromino::new(self.shape, TetrominoRotation::Ninety)
+            }
+            (MoveDirection::CCW, TetrominoRotation::TwoSeventy) => {
+                Tetromino::new(self.shape, TetrominoRotation::OneEighty)
+            }
+            (_, _) => self,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROTATIONS: [TetrominoRotation; 4] = [
+        TetrominoRotation::Zero,
+        TetrominoRotation::Ninety,
+        TetrominoRotation::OneEighty,
+        TetrominoRotation::TwoSeventy,
+    ];
+
+    fn sorted_blocks(t: Tetromino) -> Vec<(usize, usize)> {
+        let mut blocks = t.blocks.to_vec();
+        blocks.sort();
+        blocks
+    }
+
+    #[test]
+    fn test_every_state_has_four_distinct_cells_in_its_box() {
+        for shape in TetrominoShape::ALL {
+            for rotation in ROTATIONS {
+                let t = Tetromino::new(shape, rotation);
+                let mut blocks = sorted_blocks(t);
+                blocks.dedup();
+                assert_eq!(blocks.len(), 4, "{:?} {:?}", shape, rotation);
+                assert!(blocks
+                    .iter()
+                    .all(|(y, x)| *y < shape.box_size() && *x < shape.box_size()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_four_rotations_return_to_start() {
+        for shape in TetrominoShape::ALL {
+            for direction in [MoveDirection::CW, MoveDirection::CCW] {
+                let start = Tetromino::new(shape, TetrominoRotation::Zero);
+                let mut t = start;
+                for _ in 0..4 {
+                    t = t.rotate(direction);
+                }
+                assert_eq!(t.rotation, TetrominoRotation::Zero);
+                assert_eq!(sorted_blocks(t), sorted_blocks(start), "{:?}", shape);
+            }
+        }
+    }
+
+    #[test]
+    fn test_cw_then_ccw_is_identity() {
+        for shape in TetrominoShape::ALL {
+            for rotation in ROTATIONS {
+                let t = Tetromino::new(shape, rotation);
+                let back = t.rotate(MoveDirection::CW).rotate(MoveDirection::CCW);
+                assert_eq!(back.rotation, rotation);
+                assert_eq!(sorted_blocks(back), sorted_blocks(t));
+            }
+        }
+    }
+
+    #[test]
+    fn test_s_and_z_states_differ() {
+        for rotation in ROTATIONS {
+            let s = Tetromino::new(TetrominoShape::S, rotation);
+            let z = Tetromino::new(TetrominoShape::Z, rotation);
+            assert_ne!(sorted_blocks(s), sorted_blocks(z), "{:?}", rotation);
+        }
+    }
+
+    #[test]
+    fn test_srs_right_states() {
+        let t = Tetromino::new(TetrominoShape::T, TetrominoRotation::Ninety);
+        assert_eq!(sorted_blocks(t), vec![(0, 1), (1, 1), (1, 2), (2, 1)]);
+        let i = Tetromino::new(TetrominoShape::I, TetrominoRotation::Ninety);
+        assert_eq!(sorted_blocks(i), vec![(0, 2), (1, 2), (2, 2), (3, 2)]);
+        let l = Tetromino::new(TetrominoShape::L, TetrominoRotation::Ninety);
+        assert_eq!(sorted_blocks(l), vec![(0, 1), (1, 1), (2, 1), (2, 2)]);
+    }
+}